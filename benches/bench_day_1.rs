@@ -1,4 +1,4 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
 
 use aoc_2021::day_1::*;
 
@@ -11,6 +11,25 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     group.bench_function("part_2_unchecked", |b| {
         b.iter(|| black_box(part_2_unchecked(&numbers)))
     });
+    group.bench_function("part_2_simd", |b| {
+        b.iter(|| black_box(part_2_simd(black_box(&numbers))))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("day_1_synthetic");
+
+    let trace = synthetic_trace(4_000_000);
+    group.throughput(Throughput::Elements(trace.len() as u64));
+
+    for window in [1, 3] {
+        group.bench_function(format!("count_increases_{}", window), |b| {
+            b.iter(|| black_box(count_increases(black_box(&trace), window)))
+        });
+        group.bench_function(format!("count_increases_simd_{}", window), |b| {
+            b.iter(|| black_box(count_increases_simd(black_box(&trace), window)))
+        });
+    }
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
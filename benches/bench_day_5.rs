@@ -0,0 +1,85 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use aoc_2021::day_5::*;
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let input_str = include_str!("../src/day_5/input.txt");
+
+    let input = parse_input(input_str);
+
+    // The same lines, with each one moved into its own widely spaced tile so that the bounding
+    // box is far too large for a dense grid
+    let sparse_str = input_str
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let (dx, dy) = ((i % 32) as i32 * 100_000, (i / 32) as i32 * 100_000);
+            let coords = line
+                .split(" -> ")
+                .flat_map(|point| point.split(','))
+                .map(|c| c.parse::<i32>().unwrap())
+                .collect::<Vec<_>>();
+            format!(
+                "{},{} -> {},{}",
+                coords[0] + dx,
+                coords[1] + dy,
+                coords[2] + dx,
+                coords[3] + dy
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let sparse = parse_input(&sparse_str);
+
+    let mut group = c.benchmark_group("day_5");
+
+    group.bench_function("parse", |b| {
+        b.iter(|| black_box(parse_input(black_box(input_str))))
+    });
+    group.bench_function("hashmap", |b| {
+        b.iter(|| {
+            black_box(count_overlaps_hashmap::<true>(
+                black_box(&input),
+                Raster::Exact,
+            ))
+        })
+    });
+    group.bench_function("dense", |b| {
+        b.iter(|| {
+            black_box(count_overlaps_dense::<true>(
+                black_box(&input),
+                Raster::Exact,
+            ))
+        })
+    });
+    group.bench_function("sweep", |b| {
+        b.iter(|| {
+            black_box(count_overlaps_sweep::<true>(
+                black_box(&input),
+                Raster::Exact,
+            ))
+        })
+    });
+    group.bench_function("sparse_hashmap", |b| {
+        b.iter(|| {
+            black_box(count_overlaps_hashmap::<true>(
+                black_box(&sparse),
+                Raster::Exact,
+            ))
+        })
+    });
+    group.bench_function("sparse_sweep", |b| {
+        b.iter(|| {
+            black_box(count_overlaps_sweep::<true>(
+                black_box(&sparse),
+                Raster::Exact,
+            ))
+        })
+    });
+    group.bench_function("auto", |b| {
+        b.iter(|| black_box(count_overlaps::<true>(black_box(&input), Raster::Exact)))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -1,28 +1,153 @@
+use std::{io::BufRead, str::FromStr};
+
+use packed_simd::u16x16;
+
 pub fn parse_input(raw: &str) -> Vec<u16> {
     raw.lines().map(|line| line.parse().unwrap()).collect()
 }
 
-pub fn part_1(input: &[u16]) -> u16 {
+/// Yields the index of every window of `window` consecutive depths whose sum is larger than that
+/// of the window starting one position earlier.
+///
+/// Adjacent windows share all but their first and last elements, so comparing the sums reduces to
+/// comparing the first element of the earlier window with the last element of the later one.
+fn increases(depths: &[u16], window: usize) -> impl Iterator<Item = usize> + '_ {
+    assert!(window > 0, "Window size must be at least 1");
+
+    depths
+        .windows(window + 1)
+        .enumerate()
+        .filter(move |(_, w)| w[0] < w[window])
+        .map(|(i, _)| i + 1)
+}
+
+/// Counts the number of times the sum of a sliding window of `window` depths increases.
+///
+/// A window of 1 compares individual depths (part 1), a window of 3 is part 2.
+pub fn count_increases(depths: &[u16], window: usize) -> usize {
+    increases(depths, window).count()
+}
+
+/// As `count_increases`, but returns the start index of each window that was an increase on the
+/// window before it.
+pub fn increase_indices(depths: &[u16], window: usize) -> Vec<usize> {
+    increases(depths, window).collect()
+}
+
+/// A vectorised `count_increases`, comparing 16 pairs of depths per iteration.
+///
+/// Any comparisons left over after the last full vector are handled by the scalar implementation.
+pub fn count_increases_simd(depths: &[u16], window: usize) -> usize {
+    const LANES: usize = u16x16::lanes();
+
+    assert!(window > 0, "Window size must be at least 1");
+    let comparisons = depths.len().saturating_sub(window);
+    let vector_end = comparisons - comparisons % LANES;
+
     let mut count = 0;
-    let mut previous = input[0];
-    for num in &input[1..] {
-        if previous < *num {
-            count += 1;
+    for i in (0..vector_end).step_by(LANES) {
+        let earlier = u16x16::from_slice_unaligned(&depths[i..(i + LANES)]);
+        let later = u16x16::from_slice_unaligned(&depths[(i + window)..(i + window + LANES)]);
+        count += earlier.lt(later).bitmask().count_ones() as usize;
+    }
+
+    count + count_increases(&depths[vector_end..], window)
+}
+
+/// Incrementally counts window increases over a stream of depths, without needing the full trace.
+///
+/// Only the last `window` depths are retained, in a fixed size ring buffer.
+#[derive(Clone, Debug)]
+pub struct IncreaseCounter<T> {
+    window: usize,
+    ring: Vec<T>,
+
+    // The index of the oldest element of the ring, once the ring is full
+    head: usize,
+
+    count: usize,
+}
+
+impl<T: Copy + PartialOrd> IncreaseCounter<T> {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "Window size must be at least 1");
+        Self {
+            window,
+            ring: Vec::with_capacity(window),
+            head: 0,
+            count: 0,
         }
-        previous = *num;
     }
-    count
+
+    pub fn push(&mut self, depth: T) {
+        if self.ring.len() < self.window {
+            self.ring.push(depth);
+            return;
+        }
+
+        // The oldest element is the first element of the previous window, and the new depth is
+        // the last element of the next one.
+        if self.ring[self.head] < depth {
+            self.count += 1;
+        }
+
+        self.ring[self.head] = depth;
+        self.head = (self.head + 1) % self.window;
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
 }
 
-pub fn part_2(numbers: &[u16]) -> u16 {
-    let mut count = 0;
-    for i in 0..numbers.len() - 3 {
-        if numbers[i] < numbers[i + 3] {
-            count += 1;
+#[derive(Debug)]
+pub enum StreamError<E> {
+    Io(std::io::Error),
+
+    /// A line that didn't parse as a depth, numbered from 1
+    Parse {
+        line: usize,
+        err: E,
+    },
+}
+
+/// As `count_increases`, but reads one depth per line from `reader` rather than needing them all
+/// in memory up front. Blank lines are skipped.
+///
+/// The depth type is chosen by the caller, so traces with depths too large for the `u16` used by
+/// `parse_input` can be read as `u32` or `u64`.
+pub fn count_increases_streaming<T, R>(
+    reader: R,
+    window: usize,
+) -> Result<usize, StreamError<T::Err>>
+where
+    T: FromStr + Copy + PartialOrd,
+    R: BufRead,
+{
+    let mut counter = IncreaseCounter::<T>::new(window);
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(StreamError::Io)?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
         }
+
+        let depth = line
+            .parse()
+            .map_err(|err| StreamError::Parse { line: idx + 1, err })?;
+        counter.push(depth);
     }
 
-    count
+    Ok(counter.count())
+}
+
+pub fn part_1(input: &[u16]) -> usize {
+    count_increases(input, 1)
+}
+
+pub fn part_2(numbers: &[u16]) -> usize {
+    count_increases(numbers, 3)
 }
 
 pub fn part_2_unchecked(numbers: &[u16]) -> u16 {
@@ -38,4 +163,147 @@ pub fn part_2_unchecked(numbers: &[u16]) -> u16 {
     count
 }
 
-impl_day!("2021", "1", "Sonar Sweep", Vec<u16>, u16, u16);
+pub fn part_1_simd(input: &[u16]) -> usize {
+    count_increases_simd(input, 1)
+}
+
+pub fn part_2_simd(numbers: &[u16]) -> usize {
+    count_increases_simd(numbers, 3)
+}
+
+/// A random walk of depths, for exercising the counts on traces of any length
+pub fn synthetic_trace(len: usize) -> Vec<u16> {
+    let mut rng = crate::Lcg::new(0x2545_f491);
+    let mut depth = u16::MAX / 2;
+
+    (0..len)
+        .map(|_| {
+            let step = (rng.next_u16() % 21) as i32 - 10;
+            depth = (depth as i32 + step).clamp(0, u16::MAX as i32) as u16;
+            depth
+        })
+        .collect()
+}
+
+impl_day!("2021", "1", "Sonar Sweep", Vec<u16>, usize, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_input() -> Vec<u16> {
+        vec![199, 200, 208, 210, 200, 207, 240, 269, 260, 263]
+    }
+
+    #[test]
+    fn test_example() {
+        let input = example_input();
+        assert_eq!(part_1(&input), 7);
+        assert_eq!(part_2(&input), 5);
+    }
+
+    #[test]
+    fn test_increase_indices() {
+        let input = example_input();
+        assert_eq!(increase_indices(&input, 1), vec![1, 2, 3, 5, 6, 7, 9]);
+        assert_eq!(increase_indices(&input, 3), vec![1, 4, 5, 6, 7]);
+
+        // A window covering the whole trace has nothing to compare against
+        assert_eq!(count_increases(&input, input.len()), 0);
+        assert_eq!(count_increases(&input, input.len() - 1), 1);
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let input = parse_input(include_str!("./input.txt"));
+
+        for window in 1..10 {
+            let sums = input
+                .windows(window)
+                .map(|w| w.iter().map(|x| *x as u32).sum::<u32>())
+                .collect::<Vec<_>>();
+            let expected = sums.windows(2).filter(|s| s[0] < s[1]).count();
+
+            assert_eq!(count_increases(&input, window), expected);
+        }
+    }
+
+    #[test]
+    fn test_streaming() {
+        let raw = include_str!("./input.txt");
+        let input = parse_input(raw);
+
+        for window in 1..5 {
+            let streamed = count_increases_streaming::<u16, _>(raw.as_bytes(), window).unwrap();
+            assert_eq!(streamed, count_increases(&input, window));
+
+            let streamed = count_increases_streaming::<u64, _>(raw.as_bytes(), window).unwrap();
+            assert_eq!(streamed, count_increases(&input, window));
+        }
+    }
+
+    #[test]
+    fn test_counter_zero_sized() {
+        // Every depth is deeper than the last, and takes no space, so the ring never allocates
+        #[derive(Clone, Copy, PartialEq)]
+        struct Deeper;
+
+        impl PartialOrd for Deeper {
+            fn partial_cmp(&self, _: &Self) -> Option<std::cmp::Ordering> {
+                Some(std::cmp::Ordering::Less)
+            }
+        }
+
+        let mut counter = IncreaseCounter::new(2);
+        for _ in 0..5 {
+            counter.push(Deeper);
+        }
+        assert_eq!(counter.count(), 3);
+    }
+
+    #[test]
+    fn test_streaming_wide_depths() {
+        let raw = "70000\n4294967296\n\n4294967295\n5000000000\n";
+        assert_eq!(
+            count_increases_streaming::<u64, _>(raw.as_bytes(), 1).unwrap(),
+            2
+        );
+        assert_eq!(
+            count_increases_streaming::<u64, _>(raw.as_bytes(), 2).unwrap(),
+            2
+        );
+
+        match count_increases_streaming::<u32, _>(raw.as_bytes(), 1) {
+            Err(StreamError::Parse { line: 2, .. }) => (),
+            other => panic!("Expected a parse error on line 2, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        let input = parse_input(include_str!("./input.txt"));
+        assert_eq!(part_1_simd(&input), part_1(&input));
+        assert_eq!(part_2_simd(&input), part_2(&input));
+
+        // Small random steps, so that there are plenty of equal and decreasing neighbours too
+        let trace = synthetic_trace(1000);
+
+        // Cover every remainder when splitting the comparisons into vectors, including traces
+        // that are too short for even a single vector.
+        for len in 0..100 {
+            for window in 1..5 {
+                let trace = &trace[..len];
+                assert_eq!(
+                    count_increases_simd(trace, window),
+                    count_increases(trace, window),
+                    "len = {}, window = {}",
+                    len,
+                    window
+                );
+            }
+        }
+
+        assert_eq!(part_1_simd(&trace), part_1(&trace));
+        assert_eq!(part_2_simd(&trace), part_2(&trace));
+    }
+}
@@ -1,73 +1,511 @@
-enum Dir {
+use std::io::Write;
+
+use crate::Fallible;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dir {
     Forward,
     Up,
     Down,
+
+    /// Moves backwards along the current heading, the inverse of `Forward`
+    Back,
+
+    /// Returns straight to the surface, takes no number
+    Surface,
+
+    /// Moves forward while holding the current depth
+    Hold,
+}
+
+impl Dir {
+    const fn takes_num(&self) -> bool {
+        !matches!(self, Dir::Surface)
+    }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Command {
-    dir: Dir,
-    num: i32,
+    pub dir: Dir,
+
+    /// Always zero for commands that don't take a number
+    pub num: i64,
 }
 
-fn parse_input(s: &str) -> Vec<Command> {
-    fn parse_line(line: &str) -> Result<Command, ()> {
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dir = match self.dir {
+            Dir::Forward => "forward",
+            Dir::Up => "up",
+            Dir::Down => "down",
+            Dir::Back => "back",
+            Dir::Surface => "surface",
+            Dir::Hold => "hold",
+        };
+
+        if self.dir.takes_num() {
+            write!(f, "{} {}", dir, self.num)
+        } else {
+            write!(f, "{}", dir)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnknownCommand,
+    MissingNumber,
+    UnexpectedNumber,
+    InvalidNumber,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line number of the offending line, starting from 1
+    pub line: usize,
+    pub text: String,
+    pub kind: ParseErrorKind,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            ParseErrorKind::UnknownCommand => "unknown command",
+            ParseErrorKind::MissingNumber => "command requires a number",
+            ParseErrorKind::UnexpectedNumber => "command doesn't take a number",
+            ParseErrorKind::InvalidNumber => "expected a non-negative integer",
+        };
+        write!(f, "line {}: {} in {:?}", self.line, reason, self.text)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn try_parse_input(s: &str) -> Result<Vec<Command>, ParseError> {
+    fn parse_line(line: &str) -> Result<Command, ParseErrorKind> {
         let mut parts = line.splitn(2, ' ');
         let (dir_str, num_str) = match (parts.next(), parts.next()) {
-            (Some(a), Some(b)) => (a, b),
-            _ => return Err(()),
+            (Some(a), b) => (a, b),
+            _ => return Err(ParseErrorKind::UnknownCommand),
         };
 
         let dir = match dir_str {
             "forward" => Dir::Forward,
             "up" => Dir::Up,
             "down" => Dir::Down,
-            _ => return Err(()),
+            "back" => Dir::Back,
+            "surface" => Dir::Surface,
+            "hold" => Dir::Hold,
+            _ => return Err(ParseErrorKind::UnknownCommand),
         };
 
-        let num = num_str.parse().map_err(|_| ())?;
+        let num = match (dir.takes_num(), num_str) {
+            (true, Some(num_str)) => match num_str.parse() {
+                Ok(num) if num >= 0 => num,
+                _ => return Err(ParseErrorKind::InvalidNumber),
+            },
+            (true, None) => return Err(ParseErrorKind::MissingNumber),
+            (false, Some(_)) => return Err(ParseErrorKind::UnexpectedNumber),
+            (false, None) => 0,
+        };
 
         Ok(Command { dir, num })
     }
 
-    s.trim()
-        .split('\n')
-        .map(parse_line)
-        .collect::<Result<Vec<_>, _>>()
-        .expect("Expected input to parse")
+    s.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            parse_line(line.trim()).map_err(|kind| ParseError {
+                line: idx + 1,
+                text: line.to_string(),
+                kind,
+            })
+        })
+        .collect()
 }
 
-fn part_1(cmds: &[Command]) -> i32 {
-    let mut horizontal = 0;
-    let mut depth = 0;
+pub fn parse_input(s: &str) -> Vec<Command> {
+    try_parse_input(s).unwrap_or_else(|e| panic!("Expected input to parse, {}", e))
+}
 
-    for cmd in cmds {
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub horizontal: i64,
+    pub depth: i64,
+    pub aim: i64,
+}
+
+/// Defines how a single command changes the position of the submarine
+pub trait MovementModel {
+    /// Returns None if the new position can't be represented without overflowing
+    fn apply(&self, pos: &mut Position, cmd: &Command) -> Option<()>;
+}
+
+/// The model from part 1, where up and down change the depth directly
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SimpleModel;
+
+impl MovementModel for SimpleModel {
+    fn apply(&self, pos: &mut Position, cmd: &Command) -> Option<()> {
         match cmd.dir {
-            Dir::Forward => horizontal += cmd.num as i32,
-            Dir::Up => depth -= cmd.num as i32,
-            Dir::Down => depth += cmd.num as i32,
+            Dir::Forward | Dir::Hold => pos.horizontal = pos.horizontal.checked_add(cmd.num)?,
+            Dir::Back => pos.horizontal = pos.horizontal.checked_sub(cmd.num)?,
+            Dir::Up => pos.depth = pos.depth.checked_sub(cmd.num)?,
+            Dir::Down => pos.depth = pos.depth.checked_add(cmd.num)?,
+            Dir::Surface => pos.depth = 0,
         }
-    }
 
-    horizontal * depth
+        Some(())
+    }
 }
 
-fn part_2(cmds: &[Command]) -> i32 {
-    let mut aim = 0;
-    let mut horizontal = 0;
-    let mut depth = 0;
+/// The model from part 2, where up and down change the aim, and moving forward changes the depth
+/// according to the aim.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AimModel;
 
-    for cmd in cmds {
+impl MovementModel for AimModel {
+    fn apply(&self, pos: &mut Position, cmd: &Command) -> Option<()> {
         match cmd.dir {
             Dir::Forward => {
-                horizontal += cmd.num;
-                depth += cmd.num * aim;
+                pos.horizontal = pos.horizontal.checked_add(cmd.num)?;
+                pos.depth = pos.depth.checked_add(cmd.num.checked_mul(pos.aim)?)?;
+            }
+            Dir::Back => {
+                pos.horizontal = pos.horizontal.checked_sub(cmd.num)?;
+                pos.depth = pos.depth.checked_sub(cmd.num.checked_mul(pos.aim)?)?;
             }
-            Dir::Up => aim -= cmd.num,
-            Dir::Down => aim += cmd.num,
+            Dir::Hold => pos.horizontal = pos.horizontal.checked_add(cmd.num)?,
+            Dir::Up => pos.aim = pos.aim.checked_sub(cmd.num)?,
+            Dir::Down => pos.aim = pos.aim.checked_add(cmd.num)?,
+            Dir::Surface => {
+                pos.depth = 0;
+                pos.aim = 0;
+            }
+        }
+
+        Some(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowError {
+    /// A command moved the submarine out of range
+    Command {
+        /// The index of the offending command, counting from 0 since the submarine was created
+        step: usize,
+        command: Command,
+    },
+
+    /// The final position is in range, but the product of its horizontal position and depth isn't
+    Product { horizontal: i64, depth: i64 },
+}
+
+impl std::fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverflowError::Command { step, command } => write!(
+                f,
+                "submarine position overflowed at step {} ({})",
+                step, command
+            ),
+            OverflowError::Product { horizontal, depth } => write!(
+                f,
+                "product of horizontal position {} and depth {} overflowed",
+                horizontal, depth
+            ),
         }
     }
+}
 
-    horizontal * depth
+impl std::error::Error for OverflowError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrajectoryPoint {
+    pub command: Command,
+
+    /// The position of the submarine after executing the command
+    pub position: Position,
 }
 
-impl_day!("2021", "2", "Dive!", Vec<Command>, i32, i32);
+#[derive(Clone, Debug)]
+pub struct Submarine<M> {
+    model: M,
+    position: Position,
+    steps: usize,
+
+    // Only populated if the submarine was constructed with `Submarine::recording`
+    trajectory: Option<Vec<TrajectoryPoint>>,
+}
+
+impl<M: MovementModel> Submarine<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            position: Position::default(),
+            steps: 0,
+            trajectory: None,
+        }
+    }
+
+    /// Creates a submarine that records its position after every command it executes
+    pub fn recording(model: M) -> Self {
+        Self {
+            trajectory: Some(Vec::new()),
+            ..Self::new(model)
+        }
+    }
+
+    /// Executes a single command. On overflow the submarine is left where it was.
+    pub fn execute(&mut self, command: &Command) -> Result<(), OverflowError> {
+        let mut next = self.position;
+        self.model
+            .apply(&mut next, command)
+            .ok_or(OverflowError::Command {
+                step: self.steps,
+                command: *command,
+            })?;
+
+        self.position = next;
+        self.steps += 1;
+
+        if let Some(trajectory) = &mut self.trajectory {
+            trajectory.push(TrajectoryPoint {
+                command: *command,
+                position: self.position,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Executes each command in turn, stopping at the first overflow
+    pub fn execute_all<'a>(
+        &mut self,
+        commands: impl IntoIterator<Item = &'a Command>,
+    ) -> Result<(), OverflowError> {
+        for command in commands {
+            self.execute(command)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// The recorded trajectory, empty if this submarine isn't recording
+    pub fn trajectory(&self) -> &[TrajectoryPoint] {
+        self.trajectory.as_deref().unwrap_or(&[])
+    }
+
+    /// Writes the recorded trajectory as CSV, with one row per executed command
+    pub fn write_trajectory_csv(&self, mut w: impl Write) -> std::io::Result<()> {
+        writeln!(w, "step,command,horizontal,depth,aim")?;
+        for (step, point) in self.trajectory().iter().enumerate() {
+            writeln!(
+                w,
+                "{},{},{},{},{}",
+                step + 1,
+                point.command,
+                point.position.horizontal,
+                point.position.depth,
+                point.position.aim
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs all the commands, returning the product of the final horizontal position and depth
+pub fn run(model: impl MovementModel, cmds: &[Command]) -> Result<i64, OverflowError> {
+    let mut sub = Submarine::new(model);
+    sub.execute_all(cmds)?;
+
+    let pos = sub.position();
+    pos.horizontal
+        .checked_mul(pos.depth)
+        .ok_or(OverflowError::Product {
+            horizontal: pos.horizontal,
+            depth: pos.depth,
+        })
+}
+
+fn part_1(cmds: &[Command]) -> Fallible<i64, OverflowError> {
+    Fallible(run(SimpleModel, cmds))
+}
+
+fn part_2(cmds: &[Command]) -> Fallible<i64, OverflowError> {
+    Fallible(run(AimModel, cmds))
+}
+
+impl_day!(
+    "2021",
+    "2",
+    "Dive!",
+    Vec<Command>,
+    Fallible<i64, OverflowError>,
+    Fallible<i64, OverflowError>
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_input() -> Vec<Command> {
+        parse_input(
+            "forward 5
+down 5
+forward 8
+up 3
+down 8
+forward 2",
+        )
+    }
+
+    #[test]
+    fn test_example() {
+        let input = example_input();
+        assert_eq!(part_1(&input), Fallible(Ok(150)));
+        assert_eq!(part_2(&input), Fallible(Ok(900)));
+    }
+
+    #[test]
+    fn test_trajectory_csv() {
+        let mut sub = Submarine::recording(AimModel);
+        sub.execute_all(&example_input()).unwrap();
+
+        let mut csv = Vec::new();
+        sub.write_trajectory_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "step,command,horizontal,depth,aim
+1,forward 5,5,0,0
+2,down 5,5,0,5
+3,forward 8,13,40,5
+4,up 3,13,40,2
+5,down 8,13,40,10
+6,forward 2,15,60,10
+"
+        );
+
+        // Not recording, so nothing to export
+        let mut sub = Submarine::new(AimModel);
+        sub.execute_all(&example_input()).unwrap();
+        assert!(sub.trajectory().is_empty());
+        assert_eq!(sub.position().depth, 60);
+    }
+
+    #[test]
+    fn test_extra_commands() {
+        let cmds = parse_input(
+            "down 5
+forward 10
+back 4
+hold 3
+surface
+forward 2",
+        );
+
+        let mut sub = Submarine::recording(SimpleModel);
+        sub.execute_all(&cmds).unwrap();
+        let depths = sub
+            .trajectory()
+            .iter()
+            .map(|p| (p.position.horizontal, p.position.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            depths,
+            vec![(0, 5), (10, 5), (6, 5), (9, 5), (9, 0), (11, 0)]
+        );
+
+        let mut sub = Submarine::recording(AimModel);
+        sub.execute_all(&cmds).unwrap();
+        let depths = sub
+            .trajectory()
+            .iter()
+            .map(|p| (p.position.horizontal, p.position.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            depths,
+            vec![(0, 0), (10, 50), (6, 30), (9, 30), (9, 0), (11, 0)]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = try_parse_input("forward 5\nsideways 3\nup 2").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                line: 2,
+                text: "sideways 3".to_string(),
+                kind: ParseErrorKind::UnknownCommand,
+            }
+        );
+        assert_eq!(err.to_string(), "line 2: unknown command in \"sideways 3\"");
+
+        let kind = |s: &str| try_parse_input(s).unwrap_err().kind;
+        assert_eq!(kind("forward"), ParseErrorKind::MissingNumber);
+        assert_eq!(kind("surface 3"), ParseErrorKind::UnexpectedNumber);
+        assert_eq!(kind("hold x"), ParseErrorKind::InvalidNumber);
+        assert_eq!(kind("down -3"), ParseErrorKind::InvalidNumber);
+
+        assert_eq!(
+            try_parse_input("surface\nback 4").unwrap(),
+            vec![
+                Command {
+                    dir: Dir::Surface,
+                    num: 0
+                },
+                Command {
+                    dir: Dir::Back,
+                    num: 4
+                },
+            ]
+        );
+
+        // Blank lines are skipped, but still count towards the line numbers
+        assert_eq!(try_parse_input(""), Ok(vec![]));
+        assert_eq!(try_parse_input("\n\nup 2\n\n").unwrap().len(), 1);
+        assert_eq!(try_parse_input("up 2\n\nleft 1").unwrap_err().line, 3);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Fits comfortably in an i64, but the product doesn't
+        let cmds = parse_input("down 5000000000\nforward 5000000000");
+        assert_eq!(
+            run(SimpleModel, &cmds),
+            Err(OverflowError::Product {
+                horizontal: 5000000000,
+                depth: 5000000000,
+            })
+        );
+        assert_eq!(run(SimpleModel, &[]), Ok(0));
+        assert_eq!(run(AimModel, &[]), Ok(0));
+
+        // Reported in the results rather than panicking
+        assert_eq!(
+            part_1(&cmds).to_string(),
+            "error: product of horizontal position 5000000000 and depth 5000000000 overflowed"
+        );
+        assert_eq!(part_2(&cmds), Fallible(run(AimModel, &cmds)));
+
+        // The depth change from the aim overflows part way through the commands
+        let cmds = parse_input("down 5000000000\nforward 5000000000\nup 1");
+        let mut sub = Submarine::new(AimModel);
+        assert_eq!(
+            sub.execute_all(&cmds),
+            Err(OverflowError::Command {
+                step: 1,
+                command: cmds[1],
+            })
+        );
+        assert_eq!(sub.position().aim, 5000000000);
+        assert_eq!(sub.position().horizontal, 0);
+    }
+}
@@ -0,0 +1,159 @@
+#[derive(Clone, Copy, Debug)]
+pub struct StartingPositions {
+    // Zero based board positions, ie a board position of 1 in the puzzle text is stored as 0
+    p1: u8,
+    p2: u8,
+}
+
+fn parse_input(raw: &str) -> StartingPositions {
+    fn parse_line(line: &str) -> u8 {
+        let pos: u8 = line
+            .rsplit(' ')
+            .next()
+            .unwrap()
+            .parse()
+            .expect("Expected starting position to be a number");
+        assert!((1..=10).contains(&pos));
+        pos - 1
+    }
+
+    let mut lines = raw.lines();
+    let p1 = parse_line(lines.next().unwrap());
+    let p2 = parse_line(lines.next().unwrap());
+
+    StartingPositions { p1, p2 }
+}
+
+/// Moves a zero based board position forwards by the given number of spaces, wrapping at 10
+const fn advance(pos: u8, spaces: u32) -> u8 {
+    ((pos as u32 + spaces) % 10) as u8
+}
+
+fn part_1(start: &StartingPositions) -> u32 {
+    let mut pos = [start.p1, start.p2];
+    let mut score = [0u32; 2];
+
+    // The deterministic die always rolls 1, 2, 3, ..., 100, 1, 2, ...
+    let mut rolls = 0u32;
+    let mut roll = || {
+        rolls += 1;
+        (rolls - 1) % 100 + 1
+    };
+
+    let mut player = 0;
+    loop {
+        let spaces = roll() + roll() + roll();
+        pos[player] = advance(pos[player], spaces);
+        score[player] += pos[player] as u32 + 1;
+
+        if score[player] >= 1000 {
+            break;
+        }
+
+        player = 1 - player;
+    }
+
+    score[1 - player] * rolls
+}
+
+/// The number of distinct ways of rolling each total from three rolls of a three sided die,
+/// indexed by (total - 3).
+const DIRAC_ROLL_FREQS: [u64; 7] = [1, 3, 6, 7, 6, 3, 1];
+
+const DIRAC_TARGET: u8 = 21;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct DiracState {
+    // Position and score of the player who is about to roll
+    active_pos: u8,
+    active_score: u8,
+
+    // Position and score of the player who has just rolled
+    waiting_pos: u8,
+    waiting_score: u8,
+}
+
+impl DiracState {
+    const MEMO_SIZE: usize = 10 * 10 * DIRAC_TARGET as usize * DIRAC_TARGET as usize;
+
+    fn memo_idx(&self) -> usize {
+        let mut idx = self.active_pos as usize;
+        idx = idx * 10 + self.waiting_pos as usize;
+        idx = idx * DIRAC_TARGET as usize + self.active_score as usize;
+        idx = idx * DIRAC_TARGET as usize + self.waiting_score as usize;
+        idx
+    }
+}
+
+/// Counts the universes in which each player wins from the given state.
+///
+/// Returns (active player wins, waiting player wins).
+fn count_dirac_wins(state: DiracState, memo: &mut [Option<(u64, u64)>]) -> (u64, u64) {
+    if let Some(cached) = memo[state.memo_idx()] {
+        return cached;
+    }
+
+    let mut active_wins = 0;
+    let mut waiting_wins = 0;
+    for (offset, freq) in DIRAC_ROLL_FREQS.iter().enumerate() {
+        let pos = advance(state.active_pos, offset as u32 + 3);
+        let score = state.active_score + pos + 1;
+
+        if score >= DIRAC_TARGET {
+            active_wins += freq;
+        } else {
+            // Swap perspectives, the waiting player is the next to roll
+            let next = DiracState {
+                active_pos: state.waiting_pos,
+                active_score: state.waiting_score,
+                waiting_pos: pos,
+                waiting_score: score,
+            };
+            let (next_active_wins, next_waiting_wins) = count_dirac_wins(next, memo);
+            active_wins += freq * next_waiting_wins;
+            waiting_wins += freq * next_active_wins;
+        }
+    }
+
+    memo[state.memo_idx()] = Some((active_wins, waiting_wins));
+    (active_wins, waiting_wins)
+}
+
+fn part_2(start: &StartingPositions) -> u64 {
+    let mut memo = vec![None; DiracState::MEMO_SIZE];
+    let state = DiracState {
+        active_pos: start.p1,
+        active_score: 0,
+        waiting_pos: start.p2,
+        waiting_score: 0,
+    };
+
+    let (p1_wins, p2_wins) = count_dirac_wins(state, &mut memo);
+    std::cmp::max(p1_wins, p2_wins)
+}
+
+impl_day!("2021", "21", "Dirac Dice", StartingPositions, u32, u64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_input() -> StartingPositions {
+        parse_input(
+            "Player 1 starting position: 4
+Player 2 starting position: 8",
+        )
+    }
+
+    #[test]
+    fn test_part_1_example() {
+        let input = example_input();
+        assert_eq!(part_1(&input), 739785);
+    }
+
+    #[test]
+    fn test_part_2_example() {
+        let input = example_input();
+        assert_eq!(part_2(&input), 444356092776315);
+    }
+}
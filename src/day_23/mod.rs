@@ -0,0 +1,307 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use fxhash::FxHashMap;
+
+/// Amphipod types are stored as 1 (Amber) through 4 (Desert), with 0 representing an empty space
+type Cell = u8;
+
+const EMPTY: Cell = 0;
+
+const HALLWAY_LEN: usize = 11;
+
+/// The hallway positions directly outside each room. Amphipods never stop on these.
+const ROOM_ENTRANCES: [usize; 4] = [2, 4, 6, 8];
+
+/// The hallway positions that an amphipod is allowed to stop on
+const HALLWAY_STOPS: [usize; 7] = [0, 1, 3, 5, 7, 9, 10];
+
+const fn energy(amphipod: Cell) -> u32 {
+    match amphipod {
+        1 => 1,
+        2 => 10,
+        3 => 100,
+        4 => 1000,
+        _ => panic!("Not an amphipod"),
+    }
+}
+
+/// The initial room contents, the first element being the row closest to the hallway
+#[derive(Clone, Debug)]
+pub struct Diagram {
+    rows: Vec<[Cell; 4]>,
+}
+
+fn parse_input(raw: &str) -> Diagram {
+    let rows = raw
+        .lines()
+        .skip(2)
+        .filter(|line| line.bytes().any(|c| (b'A'..=b'D').contains(&c)))
+        .map(|line| {
+            let line = line.as_bytes();
+            let mut row = [EMPTY; 4];
+            for (room, entrance) in ROOM_ENTRANCES.iter().enumerate() {
+                // +1 to skip over the left hand wall
+                row[room] = match line[entrance + 1] {
+                    c @ b'A'..=b'D' => c - b'A' + 1,
+                    _ => panic!("Expected an amphipod in every room position"),
+                };
+            }
+            row
+        })
+        .collect();
+
+    Diagram { rows }
+}
+
+/// A full burrow state, packed with 3 bits per cell.
+///
+/// Cells 0 to 10 are the hallway from left to right, followed by each room in turn from the
+/// hallway end to the back wall.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Burrow<const DEPTH: usize> {
+    cells: u128,
+}
+
+impl<const DEPTH: usize> Burrow<DEPTH> {
+    const fn room_cell(room: usize, depth: usize) -> usize {
+        HALLWAY_LEN + room * DEPTH + depth
+    }
+
+    fn get(&self, cell: usize) -> Cell {
+        ((self.cells >> (cell * 3)) & 0b111) as Cell
+    }
+
+    fn set(&mut self, cell: usize, value: Cell) {
+        self.cells &= !(0b111 << (cell * 3));
+        self.cells |= (value as u128) << (cell * 3);
+    }
+
+    fn from_rows(rows: &[[Cell; 4]]) -> Self {
+        assert_eq!(rows.len(), DEPTH);
+
+        let mut burrow = Self { cells: 0 };
+        for (depth, row) in rows.iter().enumerate() {
+            for (room, amphipod) in row.iter().enumerate() {
+                burrow.set(Self::room_cell(room, depth), *amphipod);
+            }
+        }
+        burrow
+    }
+
+    fn organised() -> Self {
+        let mut burrow = Self { cells: 0 };
+        for room in 0..4 {
+            for depth in 0..DEPTH {
+                burrow.set(Self::room_cell(room, depth), room as Cell + 1);
+            }
+        }
+        burrow
+    }
+
+    /// True if every hallway cell strictly between the two positions is empty
+    fn hallway_clear(&self, from: usize, to: usize) -> bool {
+        let (low, high) = if from < to {
+            (from + 1, to)
+        } else {
+            (to + 1, from)
+        };
+        (low..high).all(|h| self.get(h) == EMPTY)
+    }
+
+    /// True if the room contains nothing but amphipods that belong in it
+    fn room_settled(&self, room: usize) -> bool {
+        (0..DEPTH).all(|depth| match self.get(Self::room_cell(room, depth)) {
+            EMPTY => true,
+            a => a as usize == room + 1,
+        })
+    }
+
+    /// A lower bound on the energy required to organise this burrow from its current state
+    ///
+    /// Every amphipod that still has to enter its destination room is charged for walking to the
+    /// room entrance, and then for filling the free slots of its room from the bottom up.
+    fn heuristic(&self) -> u32 {
+        let mut cost = 0;
+        let mut entering = [0u32; 4];
+
+        for h in 0..HALLWAY_LEN {
+            let amphipod = self.get(h);
+            if amphipod != EMPTY {
+                let entrance = ROOM_ENTRANCES[amphipod as usize - 1];
+                cost += h.abs_diff(entrance) as u32 * energy(amphipod);
+                entering[amphipod as usize - 1] += 1;
+            }
+        }
+
+        for (room, room_entrance) in ROOM_ENTRANCES.iter().enumerate() {
+            // Scan from the back wall, so that we know whether anything below needs to move out
+            let mut blocked = false;
+            for depth in (0..DEPTH).rev() {
+                let amphipod = self.get(Self::room_cell(room, depth));
+                if amphipod == EMPTY {
+                    continue;
+                }
+
+                let steps = if amphipod as usize != room + 1 {
+                    blocked = true;
+                    let entrance = ROOM_ENTRANCES[amphipod as usize - 1];
+                    depth + 1 + room_entrance.abs_diff(entrance)
+                } else if blocked {
+                    // Has to step out of the way, and then come back again
+                    depth + 1 + 2
+                } else {
+                    continue;
+                };
+
+                cost += steps as u32 * energy(amphipod);
+                entering[amphipod as usize - 1] += 1;
+            }
+        }
+
+        for (room, count) in entering.iter().enumerate() {
+            cost += count * (count + 1) / 2 * energy(room as Cell + 1);
+        }
+
+        cost
+    }
+
+    /// Calls `f` with every burrow state reachable in a single move, and the energy of that move
+    ///
+    /// Moving an amphipod from the hallway into its destination room never makes the solution
+    /// worse, so if such a move exists it is the only one produced.
+    fn for_each_move(&self, mut f: impl FnMut(Self, u32)) {
+        // Moves from the hallway into a destination room
+        for h in 0..HALLWAY_LEN {
+            let amphipod = self.get(h);
+            if amphipod == EMPTY {
+                continue;
+            }
+
+            let room = amphipod as usize - 1;
+            let entrance = ROOM_ENTRANCES[room];
+            if !self.room_settled(room) || !self.hallway_clear(h, entrance) {
+                continue;
+            }
+
+            let depth = (0..DEPTH)
+                .rev()
+                .find(|d| self.get(Self::room_cell(room, *d)) == EMPTY)
+                .expect("A settled room with an amphipod waiting to enter can't be full");
+
+            let mut next = *self;
+            next.set(h, EMPTY);
+            next.set(Self::room_cell(room, depth), amphipod);
+            let steps = h.abs_diff(entrance) + depth + 1;
+            f(next, steps as u32 * energy(amphipod));
+            return;
+        }
+
+        // Moves from the top of a room out into the hallway
+        for (room, entrance) in ROOM_ENTRANCES.iter().cloned().enumerate() {
+            if self.room_settled(room) {
+                continue;
+            }
+
+            let depth = match (0..DEPTH).find(|d| self.get(Self::room_cell(room, *d)) != EMPTY) {
+                Some(d) => d,
+                None => continue,
+            };
+            let cell = Self::room_cell(room, depth);
+            let amphipod = self.get(cell);
+
+            for h in HALLWAY_STOPS {
+                if self.get(h) != EMPTY || !self.hallway_clear(entrance, h) {
+                    continue;
+                }
+
+                let mut next = *self;
+                next.set(cell, EMPTY);
+                next.set(h, amphipod);
+                let steps = depth + 1 + h.abs_diff(entrance);
+                f(next, steps as u32 * energy(amphipod));
+            }
+        }
+    }
+}
+
+/// A* search from the given initial state to the fully organised state
+fn min_energy<const DEPTH: usize>(rows: &[[Cell; 4]]) -> u32 {
+    let start = Burrow::<DEPTH>::from_rows(rows);
+    let goal = Burrow::<DEPTH>::organised();
+
+    let mut best = FxHashMap::default();
+    best.insert(start, 0);
+
+    // Entries are (estimated total cost, cost so far, state)
+    let mut unvisited = BinaryHeap::new();
+    unvisited.push(Reverse((start.heuristic(), 0, start)));
+
+    while let Some(Reverse((_estimate, cost, burrow))) = unvisited.pop() {
+        if burrow == goal {
+            return cost;
+        }
+
+        if matches!(best.get(&burrow), Some(b) if *b < cost) {
+            // Already found something better for this state elsewhere
+            continue;
+        }
+
+        burrow.for_each_move(|next, move_cost| {
+            let next_cost = cost + move_cost;
+            if !matches!(best.get(&next), Some(b) if *b <= next_cost) {
+                best.insert(next, next_cost);
+                unvisited.push(Reverse((next_cost + next.heuristic(), next_cost, next)));
+            }
+        });
+    }
+
+    panic!("Did not find solution");
+}
+
+fn part_1(diagram: &Diagram) -> u32 {
+    min_energy::<2>(&diagram.rows)
+}
+
+fn part_2(diagram: &Diagram) -> u32 {
+    // The folded section of the diagram, revealed when the paper is unfolded
+    const FOLDED: [[Cell; 4]; 2] = [[4, 3, 2, 1], [4, 2, 1, 3]];
+
+    let first = diagram.rows[0];
+    let last = diagram.rows[diagram.rows.len() - 1];
+    min_energy::<4>(&[first, FOLDED[0], FOLDED[1], last])
+}
+
+impl_day!("2021", "23", "Amphipod", Diagram, u32, u32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_input() -> Diagram {
+        parse_input(
+            "#############
+#...........#
+###B#C#B#D###
+  #A#D#C#A#
+  #########",
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = example_input();
+        assert_eq!(input.rows, vec![[2, 3, 2, 4], [1, 4, 3, 1]]);
+    }
+
+    #[test]
+    fn test_part_1_example() {
+        let input = example_input();
+        assert_eq!(part_1(&input), 12521);
+    }
+
+    #[test]
+    fn test_part_2_example() {
+        let input = example_input();
+        assert_eq!(part_2(&input), 44169);
+    }
+}
@@ -0,0 +1,401 @@
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    W,
+    X,
+    Y,
+    Z,
+}
+
+impl Register {
+    const fn idx(&self) -> usize {
+        match self {
+            Register::W => 0,
+            Register::X => 1,
+            Register::Y => 2,
+            Register::Z => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Reg(Register),
+    Literal(i64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Inp(Register),
+    Add(Register, Operand),
+    Mul(Register, Operand),
+    Div(Register, Operand),
+    Mod(Register, Operand),
+    Eql(Register, Operand),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InstructionParseError {
+    UnknownOpcode(String),
+    InvalidRegister(String),
+    InvalidOperand(String),
+    MissingArgument,
+}
+
+impl FromStr for Register {
+    type Err = InstructionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "w" => Ok(Register::W),
+            "x" => Ok(Register::X),
+            "y" => Ok(Register::Y),
+            "z" => Ok(Register::Z),
+            _ => Err(InstructionParseError::InvalidRegister(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for Operand {
+    type Err = InstructionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(reg) = s.parse() {
+            Ok(Operand::Reg(reg))
+        } else {
+            s.parse()
+                .map(Operand::Literal)
+                .map_err(|_| InstructionParseError::InvalidOperand(s.to_string()))
+        }
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = InstructionParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_ascii_whitespace();
+        let opcode = parts.next().ok_or(InstructionParseError::MissingArgument)?;
+        let a: Register = parts
+            .next()
+            .ok_or(InstructionParseError::MissingArgument)?
+            .parse()?;
+
+        if opcode == "inp" {
+            return Ok(Instruction::Inp(a));
+        }
+
+        let b: Operand = parts
+            .next()
+            .ok_or(InstructionParseError::MissingArgument)?
+            .parse()?;
+
+        match opcode {
+            "add" => Ok(Instruction::Add(a, b)),
+            "mul" => Ok(Instruction::Mul(a, b)),
+            "div" => Ok(Instruction::Div(a, b)),
+            "mod" => Ok(Instruction::Mod(a, b)),
+            "eql" => Ok(Instruction::Eql(a, b)),
+            _ => Err(InstructionParseError::UnknownOpcode(opcode.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AluError {
+    /// An `inp` instruction was executed with no input values remaining
+    InputExhausted,
+    DivideByZero,
+    /// A `mod` instruction with a negative dividend or a non-positive divisor
+    InvalidModulo,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Alu {
+    registers: [i64; 4],
+}
+
+impl Alu {
+    pub fn get(&self, reg: Register) -> i64 {
+        self.registers[reg.idx()]
+    }
+
+    fn value(&self, operand: Operand) -> i64 {
+        match operand {
+            Operand::Reg(reg) => self.get(reg),
+            Operand::Literal(val) => val,
+        }
+    }
+
+    pub fn execute(
+        &mut self,
+        instr: Instruction,
+        input: &mut impl Iterator<Item = i64>,
+    ) -> Result<(), AluError> {
+        let (dest, result) = match instr {
+            Instruction::Inp(a) => (a, input.next().ok_or(AluError::InputExhausted)?),
+            Instruction::Add(a, b) => (a, self.get(a) + self.value(b)),
+            Instruction::Mul(a, b) => (a, self.get(a) * self.value(b)),
+            Instruction::Div(a, b) => match self.value(b) {
+                0 => return Err(AluError::DivideByZero),
+                b => (a, self.get(a) / b),
+            },
+            Instruction::Mod(a, b) => match (self.get(a), self.value(b)) {
+                (a_val, b_val) if a_val < 0 || b_val <= 0 => return Err(AluError::InvalidModulo),
+                (a_val, b_val) => (a, a_val % b_val),
+            },
+            Instruction::Eql(a, b) => (a, (self.get(a) == self.value(b)) as i64),
+        };
+
+        self.registers[dest.idx()] = result;
+        Ok(())
+    }
+
+    pub fn run(
+        program: &[Instruction],
+        input: impl IntoIterator<Item = i64>,
+    ) -> Result<Self, AluError> {
+        let mut alu = Self::default();
+        let mut input = input.into_iter();
+        for instr in program {
+            alu.execute(*instr, &mut input)?;
+        }
+
+        Ok(alu)
+    }
+}
+
+pub fn parse_input(raw: &str) -> Vec<Instruction> {
+    raw.lines()
+        .map(|line| line.parse())
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Expected puzzle input to parse")
+}
+
+const MONAD_DIGITS: usize = 14;
+const MONAD_BLOCK_LEN: usize = 18;
+
+/// The parameters that differ between each of the 14 near-identical blocks of the MONAD program.
+///
+/// Each block treats z as a stack of base-26 digits. If `pops` is set the block pops the top of
+/// the stack, then pushes `w + offset` unless `w == popped + check`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct MonadBlock {
+    pops: bool,
+    check: i64,
+    offset: i64,
+}
+
+fn monad_blocks(program: &[Instruction]) -> Vec<MonadBlock> {
+    use Instruction::*;
+    use Operand::*;
+    use Register::*;
+
+    assert_eq!(program.len(), MONAD_DIGITS * MONAD_BLOCK_LEN);
+
+    program
+        .chunks(MONAD_BLOCK_LEN)
+        .map(|block| {
+            let pops = match block[4] {
+                Div(Z, Literal(1)) => false,
+                Div(Z, Literal(26)) => true,
+                other => panic!("Unexpected MONAD instruction {:?}", other),
+            };
+            let check = match block[5] {
+                Add(X, Literal(check)) => check,
+                other => panic!("Unexpected MONAD instruction {:?}", other),
+            };
+            let offset = match block[15] {
+                Add(Y, Literal(offset)) => offset,
+                other => panic!("Unexpected MONAD instruction {:?}", other),
+            };
+
+            MonadBlock {
+                pops,
+                check,
+                offset,
+            }
+        })
+        .collect()
+}
+
+/// Finds the model number accepted by MONAD whose digits are each as close to `preferred` as
+/// possible, scanning from the most significant digit.
+///
+/// Every popping block must reject its pushed value for z to end at zero, which pairs up the
+/// digits: `w[pop] == w[push] + offset[push] + check[pop]`.
+fn solve_monad(blocks: &[MonadBlock], preferred: i64) -> [i64; MONAD_DIGITS] {
+    let mut digits = [0; MONAD_DIGITS];
+    let mut stack = Vec::new();
+
+    for (idx, block) in blocks.iter().enumerate() {
+        if !block.pops {
+            stack.push(idx);
+            continue;
+        }
+
+        let push_idx = stack.pop().expect("MONAD popped from an empty stack");
+        let diff = blocks[push_idx].offset + block.check;
+        assert!(diff.abs() < 9, "No digits satisfy the MONAD constraint");
+
+        // The push digit is the more significant of the pair, so prefer it first
+        let push_digit = preferred.clamp(1 - diff, 9 - diff);
+        digits[push_idx] = push_digit;
+        digits[idx] = push_digit + diff;
+    }
+
+    assert!(stack.is_empty(), "MONAD can never return z = 0");
+
+    digits
+}
+
+fn model_number(digits: &[i64]) -> u64 {
+    digits.iter().fold(0, |acc, d| acc * 10 + *d as u64)
+}
+
+fn find_model_number(program: &[Instruction], preferred: i64) -> u64 {
+    let digits = solve_monad(&monad_blocks(program), preferred);
+    debug_assert_eq!(
+        Alu::run(program, digits).map(|alu| alu.get(Register::Z)),
+        Ok(0)
+    );
+    model_number(&digits)
+}
+
+fn part_1(program: &[Instruction]) -> u64 {
+    find_model_number(program, 9)
+}
+
+fn part_2(program: &[Instruction]) -> u64 {
+    find_model_number(program, 1)
+}
+
+impl_day!(
+    "2021",
+    "24",
+    "Arithmetic Logic Unit",
+    Vec<Instruction>,
+    u64,
+    u64
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digits(num: u64) -> Vec<i64> {
+        num.to_string().bytes().map(|c| (c - b'0') as i64).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        use Instruction::*;
+        use Operand::*;
+        use Register::*;
+
+        assert_eq!("inp w".parse(), Ok(Inp(W)));
+        assert_eq!("add x -12".parse(), Ok(Add(X, Literal(-12))));
+        assert_eq!("mul y z".parse(), Ok(Mul(Y, Reg(Z))));
+        assert_eq!("div z 26".parse(), Ok(Div(Z, Literal(26))));
+        assert_eq!("mod x w".parse(), Ok(Mod(X, Reg(W))));
+        assert_eq!("eql x 0".parse(), Ok(Eql(X, Literal(0))));
+
+        assert_eq!(
+            "sub x 1".parse::<Instruction>(),
+            Err(InstructionParseError::UnknownOpcode("sub".to_string()))
+        );
+        assert_eq!(
+            "add q 1".parse::<Instruction>(),
+            Err(InstructionParseError::InvalidRegister("q".to_string()))
+        );
+        assert_eq!(
+            "add x one".parse::<Instruction>(),
+            Err(InstructionParseError::InvalidOperand("one".to_string()))
+        );
+        assert_eq!(
+            "add x".parse::<Instruction>(),
+            Err(InstructionParseError::MissingArgument)
+        );
+    }
+
+    #[test]
+    fn test_negate() {
+        let program = parse_input("inp x\nmul x -1");
+        let alu = Alu::run(&program, [7]).unwrap();
+        assert_eq!(alu.get(Register::X), -7);
+    }
+
+    #[test]
+    fn test_three_times_larger() {
+        let program = parse_input("inp z\ninp x\nmul z 3\neql z x");
+
+        let alu = Alu::run(&program, [3, 9]).unwrap();
+        assert_eq!(alu.get(Register::Z), 1);
+
+        let alu = Alu::run(&program, [3, 8]).unwrap();
+        assert_eq!(alu.get(Register::Z), 0);
+    }
+
+    #[test]
+    fn test_binary_conversion() {
+        let program = parse_input(
+            "inp w
+add z w
+mod z 2
+div w 2
+add y w
+mod y 2
+div w 2
+add x w
+mod x 2
+div w 2
+mod w 2",
+        );
+
+        let alu = Alu::run(&program, [0b1011]).unwrap();
+        assert_eq!(alu.get(Register::W), 1);
+        assert_eq!(alu.get(Register::X), 0);
+        assert_eq!(alu.get(Register::Y), 1);
+        assert_eq!(alu.get(Register::Z), 1);
+    }
+
+    #[test]
+    fn test_division_truncates_towards_zero() {
+        let program = parse_input("inp x\ndiv x 2");
+        let alu = Alu::run(&program, [-7]).unwrap();
+        assert_eq!(alu.get(Register::X), -3);
+    }
+
+    #[test]
+    fn test_alu_errors() {
+        let program = parse_input("inp x\ninp y");
+        assert_eq!(Alu::run(&program, [1]), Err(AluError::InputExhausted));
+
+        let program = parse_input("div x 0");
+        assert_eq!(Alu::run(&program, []), Err(AluError::DivideByZero));
+
+        let program = parse_input("inp x\nmod x 5");
+        assert_eq!(Alu::run(&program, [-1]), Err(AluError::InvalidModulo));
+
+        let program = parse_input("inp x\nmod x 0");
+        assert_eq!(Alu::run(&program, [1]), Err(AluError::InvalidModulo));
+    }
+
+    #[test]
+    fn test_solutions_accepted_by_monad() {
+        let program = parse_input(include_str!("./input.txt"));
+
+        for answer in [part_1(&program), part_2(&program)] {
+            let alu = Alu::run(&program, digits(answer)).unwrap();
+            assert_eq!(alu.get(Register::Z), 0);
+        }
+
+        // Incrementing the largest answer should produce an invalid model number
+        let too_large = digits(part_1(&program) + 1);
+        if !too_large.contains(&0) {
+            let alu = Alu::run(&program, too_large).unwrap();
+            assert_ne!(alu.get(Register::Z), 0);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+/// The sea floor, with each herd stored as a bitset per row.
+///
+/// Each row occupies `words` consecutive u64s, with the least significant bit of the first word
+/// being the leftmost cell in the row. Bits beyond `width` are always zero.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SeaFloor {
+    width: usize,
+    height: usize,
+    words: usize,
+    east: Vec<u64>,
+    south: Vec<u64>,
+}
+
+impl SeaFloor {
+    /// The range of words in either herd's bitset that make up the given row
+    fn row(&self, y: usize) -> std::ops::Range<usize> {
+        (y * self.words)..((y + 1) * self.words)
+    }
+
+    fn get(herd: &[u64], x: usize) -> bool {
+        herd[x / 64] & (1 << (x % 64)) != 0
+    }
+
+    fn set(herd: &mut [u64], x: usize) {
+        herd[x / 64] |= 1 << (x % 64);
+    }
+
+    /// Writes the row shifted one cell to the right (east) into `out`, wrapping the last cell
+    /// around to the first.
+    fn shift_east(&self, row: &[u64], out: &mut [u64]) {
+        let mut carry = Self::get(row, self.width - 1) as u64;
+        for (word, out) in row.iter().zip(out.iter_mut()) {
+            *out = (word << 1) | carry;
+            carry = word >> 63;
+        }
+        self.mask_tail(out);
+    }
+
+    /// Writes the row shifted one cell to the left (west) into `out`, wrapping the first cell
+    /// around to the last.
+    fn shift_west(&self, row: &[u64], out: &mut [u64]) {
+        let mut carry = 0;
+        for (word, out) in row.iter().zip(out.iter_mut()).rev() {
+            *out = (word >> 1) | (carry << 63);
+            carry = word & 1;
+        }
+        if carry != 0 {
+            Self::set(out, self.width - 1);
+        }
+    }
+
+    /// Clears any bits beyond the width of the row
+    fn mask_tail(&self, row: &mut [u64]) {
+        let tail_bits = self.width % 64;
+        if tail_bits != 0 {
+            row[self.words - 1] &= (1 << tail_bits) - 1;
+        }
+    }
+
+    /// Moves both herds in turn, returning true if any sea cucumber moved
+    fn step(&mut self, scratch: &mut Vec<u64>) -> bool {
+        let words = self.words;
+        let mut moved = false;
+
+        scratch.resize(words * 3, 0);
+        let (occupied, rest) = scratch.split_at_mut(words);
+        let (shifted, movers) = rest.split_at_mut(words);
+
+        // The east facing herd moves first, with each row moving independently
+        for y in 0..self.height {
+            let row = self.row(y);
+            let (east, south) = (&self.east[row.clone()], &self.south[row.clone()]);
+
+            for i in 0..words {
+                occupied[i] = east[i] | south[i];
+            }
+
+            // Bit x of shifted is now set iff the cell to the east of x is occupied
+            self.shift_west(occupied, shifted);
+            for i in 0..words {
+                movers[i] = east[i] & !shifted[i];
+            }
+
+            if movers.iter().all(|m| *m == 0) {
+                continue;
+            }
+            moved = true;
+
+            self.shift_east(movers, shifted);
+            for (i, cell) in self.east[row].iter_mut().enumerate() {
+                *cell = (*cell & !movers[i]) | shifted[i];
+            }
+        }
+
+        // The south facing herd then moves, with each row moving into the one below. The top
+        // row's occupancy is kept aside so that the bottom row sees it from before this step.
+        for (i, cell) in occupied.iter_mut().enumerate() {
+            *cell = self.east[i] | self.south[i];
+        }
+
+        // The cucumbers that moved out of the previous row, which arrive in the current row
+        let arrivals = shifted;
+        arrivals.iter_mut().for_each(|a| *a = 0);
+
+        for y in 0..self.height {
+            let row = self.row(y);
+            let next_row = self.row((y + 1) % self.height);
+
+            for i in 0..words {
+                let next_occupied = if y + 1 == self.height {
+                    occupied[i]
+                } else {
+                    self.east[next_row.start + i] | self.south[next_row.start + i]
+                };
+                movers[i] = self.south[row.start + i] & !next_occupied;
+            }
+
+            for (i, cell) in self.south[row].iter_mut().enumerate() {
+                *cell = (*cell & !movers[i]) | arrivals[i];
+            }
+
+            if movers.iter().any(|m| *m != 0) {
+                moved = true;
+            }
+            arrivals.copy_from_slice(movers);
+        }
+
+        // Cucumbers moving off the bottom row arrive on the top row
+        for (cell, arrival) in self.south[..words].iter_mut().zip(arrivals.iter()) {
+            *cell |= arrival;
+        }
+
+        moved
+    }
+}
+
+impl std::fmt::Display for SeaFloor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.height {
+            let east = &self.east[self.row(y)];
+            let south = &self.south[self.row(y)];
+            for x in 0..self.width {
+                let c = match (Self::get(east, x), Self::get(south, x)) {
+                    (true, false) => '>',
+                    (false, true) => 'v',
+                    (false, false) => '.',
+                    (true, true) => unreachable!(),
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_input(raw: &str) -> SeaFloor {
+    let width = raw.lines().next().unwrap().len();
+    let height = raw.lines().count();
+    let words = width.div_ceil(64);
+
+    let mut floor = SeaFloor {
+        width,
+        height,
+        words,
+        east: vec![0; words * height],
+        south: vec![0; words * height],
+    };
+
+    for (y, line) in raw.lines().enumerate() {
+        assert_eq!(line.len(), width);
+        let row = floor.row(y);
+        for (x, c) in line.bytes().enumerate() {
+            match c {
+                b'>' => SeaFloor::set(&mut floor.east[row.clone()], x),
+                b'v' => SeaFloor::set(&mut floor.south[row.clone()], x),
+                b'.' => (),
+                _ => panic!("Invalid input character"),
+            }
+        }
+    }
+
+    floor
+}
+
+fn part_1(input: &SeaFloor) -> u32 {
+    let mut floor = input.clone();
+    let mut scratch = Vec::new();
+
+    let mut steps = 1;
+    while floor.step(&mut scratch) {
+        steps += 1;
+    }
+
+    steps
+}
+
+fn part_2(_input: &SeaFloor) -> &'static str {
+    "Merry Christmas"
+}
+
+impl_day!("2021", "25", "Sea Cucumber", SeaFloor, u32, &'static str);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_input() -> SeaFloor {
+        parse_input(
+            "v...>>.vv>
+.vv>>.vv..
+>>.>v>...v
+>>v>>.>.v.
+v>v.vv.v..
+>.>>..v...
+.vv..>.>v.
+v.v..>>v.v
+....v..v.>",
+        )
+    }
+
+    #[test]
+    fn test_step() {
+        let mut scratch = Vec::new();
+
+        let mut floor = parse_input("...>>>>>...");
+        floor.step(&mut scratch);
+        assert_eq!(floor.to_string(), "...>>>>.>..\n");
+        floor.step(&mut scratch);
+        assert_eq!(floor.to_string(), "...>>>.>.>.\n");
+
+        let mut floor = parse_input(
+            "..........
+.>v....v..
+.......>..
+..........",
+        );
+        floor.step(&mut scratch);
+        assert_eq!(
+            floor.to_string(),
+            "..........
+.>........
+..v....v>.
+..........
+"
+        );
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let mut scratch = Vec::new();
+        let mut floor = parse_input(
+            "...>...
+.......
+......>
+v.....>
+......>
+.......
+..vvv..",
+        );
+
+        floor.step(&mut scratch);
+        assert_eq!(
+            floor.to_string(),
+            "..vv>..
+.......
+>......
+v.....>
+>......
+.......
+....v..
+"
+        );
+    }
+
+    #[test]
+    fn test_wide_rows() {
+        // Rows spanning more than one word, with a cucumber wrapping off the end of the last
+        let mut row = ".".repeat(150);
+        row.replace_range(62..66, ">>>>");
+        row.replace_range(149..150, ">");
+
+        let mut floor = parse_input(&row);
+        floor.step(&mut Vec::new());
+
+        let mut expected = ".".repeat(150);
+        expected.replace_range(0..1, ">");
+        expected.replace_range(62..67, ">>>.>");
+        assert_eq!(floor.to_string(), expected + "\n");
+    }
+
+    #[test]
+    fn test_part_1_example() {
+        let input = example_input();
+        assert_eq!(part_1(&input), 58);
+    }
+}
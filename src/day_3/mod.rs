@@ -1,99 +1,335 @@
-fn parse_input(raw: &str) -> (Vec<u16>, u16) {
-    let lines = raw
+/// A binary trie over the report values, most significant bit first, with the number of values
+/// below each node.
+#[derive(Clone, Debug)]
+pub struct BitTrie {
+    bits: u32,
+
+    // Node 0 is the root. Since the root can never be a child, a child index of 0 means there is
+    // no such child.
+    children: Vec<[u32; 2]>,
+    counts: Vec<u32>,
+}
+
+impl BitTrie {
+    fn build(values: &[u64], bits: u32) -> Self {
+        let mut trie = Self {
+            bits,
+            children: vec![[0, 0]],
+            counts: vec![0],
+        };
+
+        for value in values {
+            let mut node = 0;
+            trie.counts[node] += 1;
+
+            for bit in (0..bits).rev() {
+                let branch = ((value >> bit) & 1) as usize;
+                if trie.children[node][branch] == 0 {
+                    trie.children[node][branch] = trie.counts.len() as u32;
+                    trie.children.push([0, 0]);
+                    trie.counts.push(0);
+                }
+
+                node = trie.children[node][branch] as usize;
+                trie.counts[node] += 1;
+            }
+        }
+
+        trie
+    }
+
+    /// Finds a rating by repeatedly keeping only the values with the chosen bit, from the most
+    /// significant bit down.
+    ///
+    /// `set_bit` is given the number of remaining values with a one and with a zero in the current
+    /// position, and returns whether to keep the ones. It is only consulted when both counts are
+    /// non-zero, otherwise the only available bit is taken.
+    pub fn rating_by(&self, set_bit: impl Fn(usize, usize) -> bool) -> Option<u64> {
+        if self.counts[0] == 0 {
+            return None;
+        }
+
+        let count = |child: u32| match child {
+            0 => 0,
+            c => self.counts[c as usize] as usize,
+        };
+
+        let mut node = 0;
+        let mut val = 0u64;
+        for bit in (0..self.bits).rev() {
+            let [zero, one] = self.children[node];
+            let (zeros, ones) = (count(zero), count(one));
+
+            if zeros == 0 || (ones > 0 && set_bit(ones, zeros)) {
+                val |= 1 << bit;
+                node = one as usize;
+            } else {
+                node = zero as usize;
+            }
+        }
+
+        Some(val)
+    }
+
+    pub fn rating(&self, criteria: BitCriteria) -> Option<u64> {
+        self.rating_by(|ones, zeros| criteria.set_bit(ones, zeros))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Commonality {
+    Most,
+    Least,
+}
+
+/// Which values to keep at each bit position when searching for a rating
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitCriteria {
+    pub keep: Commonality,
+
+    /// Whether to keep the ones when there are equally many ones and zeros
+    pub tie_keeps_ones: bool,
+}
+
+impl BitCriteria {
+    pub const OXYGEN: Self = Self {
+        keep: Commonality::Most,
+        tie_keeps_ones: true,
+    };
+
+    pub const CO2: Self = Self {
+        keep: Commonality::Least,
+        tie_keeps_ones: false,
+    };
+
+    fn set_bit(&self, ones: usize, zeros: usize) -> bool {
+        if ones == zeros {
+            self.tie_keeps_ones
+        } else {
+            match self.keep {
+                Commonality::Most => ones > zeros,
+                Commonality::Least => ones < zeros,
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub values: Vec<u64>,
+    pub bits: u32,
+    pub trie: BitTrie,
+}
+
+pub fn parse_input(raw: &str) -> Report {
+    let values = raw
         .lines()
-        .map(|line| u16::from_str_radix(line, 2))
+        .map(|line| u64::from_str_radix(line, 2))
         .collect::<Result<Vec<_>, _>>()
         .unwrap();
 
-    let bits = raw.lines().next().unwrap().as_bytes().len() as u16;
+    let bits = raw.lines().next().unwrap().len() as u32;
+    assert!(bits <= 64, "Expected at most 64 bits per line");
+    assert!(
+        raw.lines().all(|line| line.len() == bits as usize),
+        "Expected every line to have the same width"
+    );
 
-    (lines, bits)
+    let trie = BitTrie::build(&values, bits);
+    Report { values, bits, trie }
 }
 
-fn part_1((input, _bits): &(Vec<u16>, u16)) -> u32 {
+/// A mask with the lowest `bits` bits set
+fn width_mask(bits: u32) -> u64 {
+    u64::MAX.checked_shr(64 - bits).unwrap_or(0)
+}
+
+pub fn part_1(report: &Report) -> u128 {
     let gamma = {
-        let mut one_freqs = [0u16; 12];
-        for line in input.iter() {
-            for bit in 0..12 {
+        let mut one_freqs = vec![0usize; report.bits as usize];
+        for line in report.values.iter() {
+            for (bit, freq) in one_freqs.iter_mut().enumerate() {
                 if line & (1 << bit) != 0 {
-                    one_freqs[bit] += 1;
+                    *freq += 1;
                 }
             }
         }
 
-        let half = input.len() as u16 / 2;
-
-        let mut gamma = 0u16;
-        for bit in 0..12 {
-            if one_freqs[bit] >= half {
+        let mut gamma = 0u64;
+        for (bit, freq) in one_freqs.iter().enumerate() {
+            // Ties count as a 1 being the most common
+            if freq * 2 >= report.values.len() {
                 gamma |= 1 << bit;
             }
         }
         gamma
     };
-    let epsilon = !gamma;
-    gamma as u32 * epsilon as u32
+    let epsilon = !gamma & width_mask(report.bits);
+    gamma as u128 * epsilon as u128
 }
 
-fn part_2((input, bits): &(Vec<u16>, u16)) -> u32 {
-    fn find(input: &[u16], bits: u16, set_bit: impl Fn(u16, u16) -> bool) -> u16 {
-        let mut val = 0u16;
+/// The original implementation of the rating search, which rescans the full input for every bit.
+///
+/// Kept for comparison against `BitTrie::rating_by`.
+pub fn rating_scan(input: &[u64], bits: u32, set_bit: impl Fn(usize, usize) -> bool) -> u64 {
+    let mut val = 0u64;
 
-        for bit in 0..bits {
-            let bit = bits - 1 - bit;
-            let existing_mask = 0xFFFFu16 << (bit + 1);
+    for bit in 0..bits {
+        let bit = bits - 1 - bit;
+        let existing_mask = u64::MAX.checked_shl(bit + 1).unwrap_or(0);
 
-            // NB, could replace these three iterations with one regular loop.
-            // The compiler may well notice that it could do that on its own anyway.
-            let count = input
-                .iter()
-                .filter(|line| **line & existing_mask == val)
-                .count() as u16;
-            let ones = input
-                .iter()
-                .filter(|line| **line & existing_mask == val)
-                .filter(|line| **line & (1 << bit) > 0)
-                .count() as u16;
-
-            if count == 1 {
-                return *input
-                    .iter()
-                    .filter(|line| *line & existing_mask == val)
-                    .next()
-                    .unwrap();
-            }
+        // NB, could replace these three iterations with one regular loop.
+        // The compiler may well notice that it could do that on its own anyway.
+        let count = input
+            .iter()
+            .filter(|line| **line & existing_mask == val)
+            .count();
+        let ones = input
+            .iter()
+            .filter(|line| **line & existing_mask == val)
+            .filter(|line| **line & (1 << bit) > 0)
+            .count();
 
-            if set_bit(ones, count - ones) {
-                val |= 1 << bit;
-            }
+        if count == 1 {
+            return *input
+                .iter()
+                .find(|line| *line & existing_mask == val)
+                .unwrap();
         }
 
-        val
+        // Never pick a bit value that none of the remaining values have
+        let zeros = count - ones;
+        if zeros == 0 || (ones > 0 && set_bit(ones, zeros)) {
+            val |= 1 << bit;
+        }
     }
 
-    let oxy = find(input, *bits, |ones, zeros| ones >= zeros) as u32;
-    let co2 = find(input, *bits, |ones, zeros| ones < zeros) as u32;
-    oxy * co2
+    val
+}
+
+pub fn part_2_scan(report: &Report) -> u128 {
+    let oxy = rating_scan(&report.values, report.bits, |ones, zeros| ones >= zeros);
+    let co2 = rating_scan(&report.values, report.bits, |ones, zeros| ones < zeros);
+    oxy as u128 * co2 as u128
+}
+
+pub fn part_2(report: &Report) -> u128 {
+    let oxy = report.trie.rating(BitCriteria::OXYGEN).unwrap();
+    let co2 = report.trie.rating(BitCriteria::CO2).unwrap();
+    oxy as u128 * co2 as u128
 }
 
-impl_day!("2021", "3", "Binary Diagnostic", (Vec<u16>, u16), u32, u32);
+impl_day!("2021", "3", "Binary Diagnostic", Report, u128, u128);
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    fn test_input() -> (Vec<u16>, u16) {
-        (
-            vec![
-                0b00100u16, 0b11110u16, 0b10110u16, 0b10111u16, 0b10101u16, 0b01111u16, 0b00111u16,
-                0b11100u16, 0b10000u16, 0b11001u16, 0b00010u16, 0b01010u16,
-            ],
-            5,
+    fn test_input() -> Report {
+        parse_input(
+            "00100
+11110
+10110
+10111
+10101
+01111
+00111
+11100
+10000
+11001
+00010
+01010",
         )
     }
 
+    #[test]
+    fn test_parse() {
+        let report = test_input();
+        assert_eq!(report.bits, 5);
+        assert_eq!(report.values[..3], [0b00100, 0b11110, 0b10110]);
+    }
+
+    #[test]
+    fn test_part_1() {
+        assert_eq!(part_1(&test_input()), 198)
+    }
+
     #[test]
     fn test_part_2() {
-        assert_eq!(part_2(&test_input()), 230)
+        assert_eq!(part_2(&test_input()), 230);
+        assert_eq!(part_2_scan(&test_input()), 230);
+    }
+
+    #[test]
+    fn test_ratings() {
+        let report = test_input();
+        assert_eq!(report.trie.rating(BitCriteria::OXYGEN), Some(0b10111));
+        assert_eq!(report.trie.rating(BitCriteria::CO2), Some(0b01010));
+
+        // Flipping the tie breaks only matters where there are ties
+        let oxy_ties_zero = BitCriteria {
+            tie_keeps_ones: false,
+            ..BitCriteria::OXYGEN
+        };
+        assert_eq!(report.trie.rating(oxy_ties_zero), Some(0b10110));
+
+        let co2_ties_one = BitCriteria {
+            tie_keeps_ones: true,
+            ..BitCriteria::CO2
+        };
+        assert_eq!(report.trie.rating(co2_ties_one), Some(0b01111));
+
+        let empty = BitTrie::build(&[], 5);
+        assert_eq!(empty.rating(BitCriteria::OXYGEN), None);
+    }
+
+    #[test]
+    fn test_trie_matches_scan() {
+        let report = parse_input(include_str!("./input.txt"));
+        assert_eq!(part_2(&report), part_2_scan(&report));
+
+        // Check against scans with every combination of criteria
+        for keep in [Commonality::Most, Commonality::Least] {
+            for tie_keeps_ones in [false, true] {
+                let criteria = BitCriteria {
+                    keep,
+                    tie_keeps_ones,
+                };
+                assert_eq!(
+                    report.trie.rating(criteria),
+                    Some(rating_scan(&report.values, report.bits, |ones, zeros| {
+                        criteria.set_bit(ones, zeros)
+                    }))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_values() {
+        // The 5 bit example, with an extra 59 bits of ones on the front of every value
+        let padding = width_mask(59) << 5;
+        let values = test_input()
+            .values
+            .iter()
+            .map(|x| x | padding)
+            .collect::<Vec<_>>();
+        let trie = BitTrie::build(&values, 64);
+        let report = Report {
+            values,
+            bits: 64,
+            trie,
+        };
+
+        // Gamma gains all the ones on the front, epsilon gains nothing
+        let gamma = 0b10110 | padding;
+        let epsilon = 0b01001;
+        assert_eq!(part_1(&report), gamma as u128 * epsilon as u128);
+
+        // The ratings just gain the ones on the front
+        let oxy = 0b10111 | padding;
+        let co2 = 0b01010 | padding;
+        assert_eq!(part_2(&report), oxy as u128 * co2 as u128);
+        assert_eq!(part_2_scan(&report), oxy as u128 * co2 as u128);
     }
 }
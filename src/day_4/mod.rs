@@ -1,59 +1,200 @@
-use std::collections::HashSet;
+use fxhash::FxHashMap;
 
-const fn winning_masks() -> [u32; 10] {
-    let mut m = [0; 10];
+use crate::Fallible;
 
-    let mut row = 0;
-    while row < 5 {
-        m[row] = 0b11111 << (5 * row);
-        row += 1;
-    }
+/// The conditions under which a board counts as having won
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WinRule {
+    /// A complete row or column, and optionally either of the two main diagonals
+    Lines { diagonals: bool },
 
-    let mut col = 0;
-    while col < 5 {
-        m[col + 5] = 0b00001_00001_00001_00001_00001 << col;
-        col += 1;
-    }
+    /// Every number on the board
+    Blackout,
+}
+
+impl WinRule {
+    /// The rule from the puzzle
+    pub const STANDARD: Self = WinRule::Lines { diagonals: false };
+}
+
+/// What marking a single cell completed, to check for a win without rescanning the board
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Completed {
+    /// The cell's row or column
+    line: bool,
+
+    /// Either diagonal through the cell
+    diagonal: bool,
 
-    m
+    /// The whole board
+    blackout: bool,
 }
 
-const WINNING_MASKS: [u32; 10] = winning_masks();
+impl Completed {
+    fn wins(&self, rule: WinRule) -> bool {
+        match rule {
+            WinRule::Lines { diagonals } => self.line || (diagonals && self.diagonal),
+            WinRule::Blackout => self.blackout,
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Board {
-    values: [i32; 25],
+    size: usize,
+
+    // Row major, size * size values
+    values: Vec<i32>,
+    marked: Vec<bool>,
 
-    // Bitmask, the least significant bit maps to the first element of values
-    marked: u32,
+    // The number of marked cells in each row, each column, and the leading and trailing diagonals
+    row_marks: Vec<usize>,
+    col_marks: Vec<usize>,
+    diag_marks: [usize; 2],
+    total_marks: usize,
 }
 
 impl Board {
-    fn mark(&mut self, num: i32) {
-        if let Some(i) = self.values.iter().position(|x| *x == num) {
-            self.marked |= 1 << i;
+    fn new(size: usize, values: Vec<i32>) -> Self {
+        assert_eq!(values.len(), size * size);
+        Self {
+            size,
+            marked: vec![false; values.len()],
+            values,
+            row_marks: vec![0; size],
+            col_marks: vec![0; size],
+            diag_marks: [0; 2],
+            total_marks: 0,
         }
     }
 
-    fn winning(&self) -> bool {
-        for mask in WINNING_MASKS.iter() {
-            if self.marked & *mask == *mask {
-                return true;
-            }
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Marks a cell, returning what that completed. Marking a cell a second time completes nothing.
+    fn mark_cell(&mut self, idx: usize) -> Completed {
+        let mut completed = Completed::default();
+        if self.marked[idx] {
+            return completed;
         }
 
-        false
+        let (row, col) = (idx / self.size, idx % self.size);
+        self.marked[idx] = true;
+        self.row_marks[row] += 1;
+        self.col_marks[col] += 1;
+        completed.line = self.row_marks[row] == self.size || self.col_marks[col] == self.size;
+        if row == col {
+            self.diag_marks[0] += 1;
+            completed.diagonal |= self.diag_marks[0] == self.size;
+        }
+        if row + col == self.size - 1 {
+            self.diag_marks[1] += 1;
+            completed.diagonal |= self.diag_marks[1] == self.size;
+        }
+        self.total_marks += 1;
+        completed.blackout = self.total_marks == self.values.len();
+
+        completed
+    }
+
+    pub fn winning(&self, rule: WinRule) -> bool {
+        let full = |marks: &usize| *marks == self.size;
+
+        match rule {
+            WinRule::Lines { diagonals } => {
+                self.row_marks.iter().any(full)
+                    || self.col_marks.iter().any(full)
+                    || (diagonals && self.diag_marks.iter().any(full))
+            }
+            WinRule::Blackout => self.total_marks == self.values.len(),
+        }
     }
 
-    fn unmarked_sum(&self) -> i32 {
-        (0..25)
-            .filter(|i| self.marked & (1 << *i) == 0)
-            .map(|i| self.values[i])
+    pub fn unmarked_sum(&self) -> i32 {
+        self.values
+            .iter()
+            .zip(self.marked.iter())
+            .filter(|(_, marked)| !**marked)
+            .map(|(value, _)| *value)
             .sum()
     }
 }
 
-fn parse_input(raw: &str) -> (Vec<i32>, Vec<Board>) {
+impl std::fmt::Display for Board {
+    /// Renders the board one row per line, with marked numbers in square brackets
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .values
+            .iter()
+            .map(|v| v.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        for row in 0..self.size {
+            for col in 0..self.size {
+                let idx = row * self.size + col;
+                if col > 0 {
+                    write!(f, " ")?;
+                }
+
+                if self.marked[idx] {
+                    write!(f, "[{:>width$}]", self.values[idx], width = width)?;
+                } else {
+                    write!(f, " {:>width$} ", self.values[idx], width = width)?;
+                }
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Bingo {
+    pub numbers: Vec<i32>,
+    pub boards: Vec<Board>,
+
+    // Every (board index, cell index) that holds each number, in board order
+    cells_by_number: FxHashMap<i32, Vec<(usize, usize)>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BingoError {
+    NoBoards,
+
+    /// The boards that are still incomplete once every number has been drawn
+    NeverWins {
+        boards: Vec<usize>,
+    },
+}
+
+impl std::fmt::Display for BingoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BingoError::NoBoards => write!(f, "no boards"),
+            BingoError::NeverWins { boards } => write!(f, "boards {:?} never win", boards),
+        }
+    }
+}
+
+impl std::error::Error for BingoError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Win {
+    pub board: usize,
+
+    /// The index into the draw order of the number that completed the board
+    pub draw: usize,
+    pub number: i32,
+    pub score: i32,
+}
+
+/// Parses the draw order followed by any number of square boards, each preceded by a blank line.
+///
+/// The board size is taken from the number of values on the first row of the first board.
+pub fn parse_input(raw: &str) -> Bingo {
     let mut lines = raw.lines();
     let numbers = lines
         .next()
@@ -64,81 +205,350 @@ fn parse_input(raw: &str) -> (Vec<i32>, Vec<Board>) {
         .unwrap();
 
     let mut boards = Vec::new();
+    let mut size = None;
+    let mut values = Vec::new();
+
+    for line in lines {
+        let row = line
+            .split_ascii_whitespace()
+            .map(|s| s.parse())
+            .collect::<Result<Vec<i32>, _>>()
+            .unwrap();
 
-    loop {
-        // Consume the blank line
-        if let None = lines.next() {
-            break;
+        if row.is_empty() {
+            // Blank line between boards
+            continue;
         }
-        let mut board = Board {
-            values: [0; 25],
-            marked: 0u32,
-        };
 
-        for row in 0..5 {
-            let offset = 5 * row;
-            let dest = &mut board.values[offset..(offset + 5)];
-            for (i, num) in lines
-                .next()
-                .unwrap()
-                .split_ascii_whitespace()
-                .map(|s| s.parse())
-                .map(Result::unwrap)
-                .enumerate()
-            {
-                dest[i] = num;
-            }
+        let size = *size.get_or_insert(row.len());
+        assert_eq!(
+            row.len(),
+            size,
+            "Expected every board row to be the same width"
+        );
+
+        values.extend(row);
+        if values.len() == size * size {
+            boards.push(Board::new(size, std::mem::take(&mut values)));
         }
+    }
+
+    assert!(values.is_empty(), "Expected the last board to be complete");
 
-        boards.push(board);
+    let mut cells_by_number = FxHashMap::default();
+    for (board_idx, board) in boards.iter().enumerate() {
+        for (cell_idx, value) in board.values.iter().enumerate() {
+            cells_by_number
+                .entry(*value)
+                .or_insert_with(Vec::new)
+                .push((board_idx, cell_idx));
+        }
     }
 
-    (numbers, boards)
+    Bingo {
+        numbers,
+        boards,
+        cells_by_number,
+    }
 }
 
-fn part_1((numbers, boards): &(Vec<i32>, Vec<Board>)) -> i32 {
-    let mut boards = boards.clone();
-    for number in numbers.iter() {
-        for board in boards.iter_mut() {
-            board.mark(*number);
-            if board.winning() {
-                return board.unmarked_sum() * *number;
+impl Bingo {
+    fn cells(&self, number: i32) -> &[(usize, usize)] {
+        self.cells_by_number
+            .get(&number)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Plays the full game, returning every board that wins in the order that they win.
+    ///
+    /// Boards that win on the same draw are ordered by board index. Boards stop being marked once
+    /// they have won, and boards that never win are absent from the result.
+    pub fn simulate(&self, rule: WinRule) -> Vec<Win> {
+        let mut boards = self.boards.clone();
+        let mut won = vec![false; boards.len()];
+        let mut wins = Vec::new();
+
+        for (draw, number) in self.numbers.iter().enumerate() {
+            for (board_idx, cell_idx) in self.cells(*number) {
+                if won[*board_idx] {
+                    continue;
+                }
+
+                // Boards are no longer marked once they've won, so the mark that completes
+                // something is the winning one
+                let board = &mut boards[*board_idx];
+                if board.mark_cell(*cell_idx).wins(rule) {
+                    won[*board_idx] = true;
+                    wins.push(Win {
+                        board: *board_idx,
+                        draw,
+                        number: *number,
+                        score: board.unmarked_sum() * *number,
+                    });
+                }
+            }
+
+            if wins.len() == boards.len() {
+                break;
             }
         }
+
+        wins
     }
 
-    panic!("No winners");
-}
+    /// The first win for every board, indexed by board. None for boards that never win.
+    pub fn board_outcomes(&self, rule: WinRule) -> Vec<Option<Win>> {
+        let mut outcomes = vec![None; self.boards.len()];
+        for win in self.simulate(rule) {
+            outcomes[win.board] = Some(win);
+        }
+
+        outcomes
+    }
+
+    pub fn first_winner(&self, rule: WinRule) -> Option<Win> {
+        self.simulate(rule).first().cloned()
+    }
 
-fn part_2((numbers, boards): &(Vec<i32>, Vec<Board>)) -> i32 {
-    let mut boards = boards.clone();
-    let mut losing_boards = (0..(boards.len())).collect::<HashSet<_>>();
+    /// The last board to win, provided that every board does eventually win
+    pub fn last_winner(&self, rule: WinRule) -> Result<Win, BingoError> {
+        let wins = self.simulate(rule);
 
-    let mut last_win = None;
-    let mut to_remove = Vec::new();
-    for number in numbers.iter() {
-        to_remove.clear();
-        for board in losing_boards.iter() {
-            boards[*board].mark(*number);
-            if boards[*board].winning() {
-                to_remove.push(*board);
-                last_win = Some((*board, *number));
+        if self.boards.is_empty() {
+            Err(BingoError::NoBoards)
+        } else if wins.len() < self.boards.len() {
+            let mut won = vec![false; self.boards.len()];
+            for win in &wins {
+                won[win.board] = true;
             }
-        }
 
-        for board in to_remove.iter() {
-            losing_boards.remove(board);
+            let boards = (0..self.boards.len()).filter(|idx| !won[*idx]).collect();
+            Err(BingoError::NeverWins { boards })
+        } else {
+            Ok(*wins.last().unwrap())
         }
+    }
 
-        if losing_boards.len() == 0 {
-            break;
+    /// The state of a board after the first `draws` numbers have been drawn
+    pub fn board_at(&self, board: usize, draws: usize) -> Board {
+        let mut state = self.boards[board].clone();
+        for number in &self.numbers[..draws] {
+            for (board_idx, cell_idx) in self.cells(*number) {
+                if *board_idx == board {
+                    state.mark_cell(*cell_idx);
+                }
+            }
         }
+
+        state
     }
 
-    match last_win {
-        Some((idx, number)) => boards[idx].unmarked_sum() * number,
+    /// A text rendering of a board after the first `draws` numbers have been drawn
+    pub fn replay(&self, board: usize, draws: usize, rule: WinRule) -> String {
+        let state = self.board_at(board, draws);
+        let last_drawn = match draws {
+            0 => "nothing drawn yet".to_string(),
+            _ => format!("last drawn {}", self.numbers[draws - 1]),
+        };
+        let status = if state.winning(rule) { ", winning" } else { "" };
+
+        format!(
+            "Board {} after {} draws ({}{})\n{}",
+            board, draws, last_drawn, status, state
+        )
+    }
+}
+
+fn part_1(bingo: &Bingo) -> i32 {
+    match bingo.first_winner(WinRule::STANDARD) {
+        Some(win) => win.score,
         None => panic!("No winners"),
     }
 }
 
-impl_day!("2021", "4", "Giant Squid", (Vec<i32>, Vec<Board>), i32, i32);
+fn part_2(bingo: &Bingo) -> Fallible<i32, BingoError> {
+    Fallible(bingo.last_winner(WinRule::STANDARD).map(|win| win.score))
+}
+
+impl_day!(
+    "2021",
+    "4",
+    "Giant Squid",
+    Bingo,
+    i32,
+    Fallible<i32, BingoError>
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_input() -> Bingo {
+        parse_input(
+            "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+
+22 13 17 11  0
+ 8  2 23  4 24
+21  9 14 16  7
+ 6 10  3 18  5
+ 1 12 20 15 19
+
+ 3 15  0  2 22
+ 9 18 13 17  5
+19  8  7 25 23
+20 11 10 24  4
+14 21 16 12  6
+
+14 21 17 24  4
+10 16 15  9 19
+18  8 23 26 20
+22 11 13  6  5
+ 2  0 12  3  7
+",
+        )
+    }
+
+    #[test]
+    fn test_example() {
+        let input = example_input();
+        assert_eq!(input.boards.len(), 3);
+        assert_eq!(part_1(&input), 4512);
+        assert_eq!(part_2(&input), Fallible(Ok(1924)));
+    }
+
+    #[test]
+    fn test_win_rules() {
+        let input = parse_input(
+            "5,1,9,2,3,4,6,7,8
+
+1 2 3
+4 5 6
+7 8 9
+
+9 8 7
+6 5 4
+3 2 1",
+        );
+        assert_eq!(input.boards[0].size(), 3);
+
+        let board_scores = |rule| {
+            input
+                .simulate(rule)
+                .iter()
+                .map(|win| (win.board, win.score))
+                .collect::<Vec<_>>()
+        };
+
+        // Without diagonals, both boards complete a row on the 5th draw (3)
+        assert_eq!(
+            board_scores(WinRule::STANDARD),
+            vec![(0, 25 * 3), (1, 25 * 3)]
+        );
+
+        // With diagonals, both boards complete the 1-5-9 diagonal on the 3rd draw (9)
+        let diagonals = WinRule::Lines { diagonals: true };
+        assert_eq!(board_scores(diagonals), vec![(0, 30 * 9), (1, 30 * 9)]);
+
+        // Both boards need every number
+        assert_eq!(board_scores(WinRule::Blackout), vec![(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn test_win_order() {
+        let input = example_input();
+        assert_eq!(
+            input.simulate(WinRule::STANDARD),
+            vec![
+                Win {
+                    board: 2,
+                    draw: 11,
+                    number: 24,
+                    score: 4512,
+                },
+                Win {
+                    board: 0,
+                    draw: 13,
+                    number: 16,
+                    score: 2192,
+                },
+                Win {
+                    board: 1,
+                    draw: 14,
+                    number: 13,
+                    score: 1924,
+                },
+            ]
+        );
+        // The marks that complete something agree with a full scan of the board
+        let real = parse_input(include_str!("./input.txt"));
+        for rule in [
+            WinRule::STANDARD,
+            WinRule::Lines { diagonals: true },
+            WinRule::Blackout,
+        ] {
+            for win in real.simulate(rule) {
+                assert!(!real.board_at(win.board, win.draw).winning(rule));
+                assert!(real.board_at(win.board, win.draw + 1).winning(rule));
+            }
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let input = example_input();
+        assert_eq!(
+            input.replay(2, 12, WinRule::STANDARD),
+            "Board 2 after 12 draws (last drawn 24, winning)
+[14] [21] [17] [24] [ 4]
+ 10   16   15  [ 9]  19 
+ 18    8  [23]  26   20 
+ 22  [11]  13    6  [ 5]
+[ 2] [ 0]  12    3  [ 7]
+"
+        );
+
+        assert_eq!(
+            input.replay(0, 0, WinRule::STANDARD).lines().next(),
+            Some("Board 0 after 0 draws (nothing drawn yet)")
+        );
+    }
+
+    #[test]
+    fn test_never_wins() {
+        // Board 1 never has a full line, board 2 only completes on the last draw
+        let input = parse_input(
+            "1,2,3,4
+
+1 2
+5 6
+
+5 6
+7 8
+
+7 4
+8 3",
+        );
+
+        let outcomes = input.board_outcomes(WinRule::STANDARD);
+        assert_eq!(outcomes[0].map(|win| win.draw), Some(1));
+        assert_eq!(outcomes[1], None);
+        assert_eq!(outcomes[2].map(|win| win.draw), Some(3));
+
+        assert_eq!(
+            part_2(&input),
+            Fallible(Err(BingoError::NeverWins { boards: vec![1] }))
+        );
+        assert_eq!(part_2(&input).to_string(), "error: boards [1] never win");
+
+        // No board has all of its numbers drawn
+        let blackout = input.board_outcomes(WinRule::Blackout);
+        assert!(blackout.iter().all(Option::is_none));
+
+        let empty = parse_input("1,2,3");
+        assert_eq!(
+            empty.last_winner(WinRule::STANDARD),
+            Err(BingoError::NoBoards)
+        );
+    }
+}
@@ -1,9 +1,9 @@
 use std::collections::HashMap;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-struct IntVec {
-    x: i32,
-    y: i32,
+pub struct IntVec {
+    pub x: i32,
+    pub y: i32,
 }
 
 impl std::ops::Add for IntVec {
@@ -17,37 +17,154 @@ impl std::ops::Add for IntVec {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+/// How a line is turned into the set of points it covers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Raster {
+    /// Only the lattice points lying exactly on the line
+    Exact,
+
+    /// One point for each step along the line's longer axis, the nearest to the true line, as
+    /// produced by Bresenham's algorithm. Ties are rounded away from the start of the line.
+    Bresenham,
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// The ceiling of a / b, for positive b
+fn div_ceil(a: i64, b: i64) -> i64 {
+    -(-a).div_euclid(b)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Line {
-    start: IntVec,
-    end: IntVec,
+    pub start: IntVec,
+    pub end: IntVec,
 }
 
 impl Line {
-    const fn dir(&self) -> IntVec {
+    const fn delta(&self) -> IntVec {
         IntVec {
-            x: (self.end.x - self.start.x).signum(),
-            y: (self.end.y - self.start.y).signum(),
+            x: self.end.x - self.start.x,
+            y: self.end.y - self.start.y,
         }
     }
 
-    fn iter_points(&self) -> LineIter {
-        LineIter::new(*self)
+    /// The number of steps from the start to the end of the line under the given raster
+    fn steps(&self, raster: Raster) -> i32 {
+        let d = self.delta();
+        match raster {
+            Raster::Exact => gcd(d.x.abs() as i64, d.y.abs() as i64) as i32,
+            Raster::Bresenham => std::cmp::max(d.x.abs(), d.y.abs()),
+        }
+    }
+
+    /// The point reached after `i` of the given number of steps along the line
+    fn point_at(&self, i: i32, steps: i32, raster: Raster) -> IntVec {
+        if steps == 0 {
+            return self.start;
+        }
+
+        let d = self.delta();
+        let offset = |d: i32| -> i32 {
+            match raster {
+                Raster::Exact => d / steps * i,
+                Raster::Bresenham => {
+                    let (i, d, steps) = (i as i64, d as i64, steps as i64);
+                    (d.signum() * ((2 * i * d.abs() + steps) / (2 * steps))) as i32
+                }
+            }
+        };
+
+        IntVec {
+            x: self.start.x + offset(d.x),
+            y: self.start.y + offset(d.y),
+        }
+    }
+
+    pub fn points(&self, raster: Raster) -> LineIter {
+        LineIter::new(*self, raster)
+    }
+
+    /// True for the horizontal, vertical and 45° lines the puzzle promises
+    pub fn is_axis_or_diagonal(&self) -> bool {
+        let d = self.delta();
+        d.x == 0 || d.y == 0 || d.x.abs() == d.y.abs()
+    }
+
+    fn is_diagonal(&self) -> bool {
+        let d = self.delta();
+        d.x != 0 && d.y != 0
+    }
+
+    fn min_y(&self) -> i32 {
+        std::cmp::min(self.start.y, self.end.y)
+    }
+
+    fn max_y(&self) -> i32 {
+        std::cmp::max(self.start.y, self.end.y)
+    }
+
+    /// The inclusive range of x values covered by this line on row y, which must lie between the
+    /// ends of the line.
+    ///
+    /// Under either raster the points on a single row are contiguous.
+    fn x_span_at(&self, y: i32, raster: Raster) -> Option<(i32, i32)> {
+        let d = self.delta();
+        if d.y == 0 {
+            return Some((
+                std::cmp::min(self.start.x, self.end.x),
+                std::cmp::max(self.start.x, self.end.x),
+            ));
+        }
+
+        let steps = self.steps(raster);
+        let k = y - self.start.y;
+        let (first, last) = match raster {
+            Raster::Exact => {
+                let step_y = d.y / steps;
+                if k % step_y != 0 {
+                    return None;
+                }
+                (k / step_y, k / step_y)
+            }
+            Raster::Bresenham => {
+                // Invert the rounding in `point_at` to find the steps that land on this row
+                let (k, dy, n) = (k.abs() as i64, d.y.abs() as i64, steps as i64);
+                let first = std::cmp::max(div_ceil(2 * n * k - n, 2 * dy), 0);
+                let last = std::cmp::min(div_ceil(2 * n * k + n, 2 * dy) - 1, n);
+                if first > last {
+                    return None;
+                }
+                (first as i32, last as i32)
+            }
+        };
+
+        let a = self.point_at(first, steps, raster).x;
+        let b = self.point_at(last, steps, raster).x;
+        Some((std::cmp::min(a, b), std::cmp::max(a, b)))
     }
 }
 
-struct LineIter {
+pub struct LineIter {
     line: Line,
-    pos: IntVec,
-    dir: IntVec,
+    raster: Raster,
+    next: i32,
+    steps: i32,
 }
 
 impl LineIter {
-    fn new(line: Line) -> Self {
+    fn new(line: Line, raster: Raster) -> Self {
         Self {
             line,
-            dir: line.dir(),
-            pos: line.start,
+            raster,
+            next: 0,
+            steps: line.steps(raster),
         }
     }
 }
@@ -56,61 +173,907 @@ impl Iterator for LineIter {
     type Item = IntVec;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos == self.line.end + self.dir {
+        if self.next > self.steps {
             None
         } else {
-            let ret = self.pos;
-            self.pos = self.pos + self.dir;
+            let ret = self.line.point_at(self.next, self.steps, self.raster);
+            self.next += 1;
             Some(ret)
         }
     }
 }
 
-fn parse_input(raw: &str) -> Vec<Line> {
-    fn parse_intvec(s: &str) -> IntVec {
-        let mut parts = s.split(",");
-        let x = parts.next().unwrap().parse().unwrap();
-        let y = parts.next().unwrap().parse().unwrap();
-        IntVec { x, y }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    Malformed,
+    UnsupportedAngle,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line number of the offending line, starting from 1
+    pub line: usize,
+    pub text: String,
+    pub kind: ParseErrorKind,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            ParseErrorKind::Malformed => "expected \"x1,y1 -> x2,y2\"",
+            ParseErrorKind::UnsupportedAngle => "line is not horizontal, vertical or at 45°",
+        };
+        write!(f, "line {}: {} in {:?}", self.line, reason, self.text)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses the vent lines, rejecting any that aren't horizontal, vertical or at 45° unless
+/// `any_angle` is set.
+pub fn try_parse_input(raw: &str, any_angle: bool) -> Result<Vec<Line>, ParseError> {
+    fn parse_intvec(s: &str) -> Option<IntVec> {
+        let (x, y) = s.split_once(',')?;
+        Some(IntVec {
+            x: x.parse().ok()?,
+            y: y.parse().ok()?,
+        })
     }
 
-    let mut lines = Vec::new();
-    for line in raw.lines() {
-        let mut parts = line.split_ascii_whitespace();
-        let start = parse_intvec(parts.next().unwrap());
-        parts.next();
-        let end = parse_intvec(parts.next().unwrap());
-        lines.push(Line { start, end });
+    fn parse_line(line: &str) -> Option<Line> {
+        let (start, end) = line.split_once(" -> ")?;
+        Some(Line {
+            start: parse_intvec(start)?,
+            end: parse_intvec(end)?,
+        })
     }
 
-    lines
+    raw.lines()
+        .enumerate()
+        .map(|(idx, text)| {
+            let kind = match parse_line(text) {
+                Some(line) if any_angle || line.is_axis_or_diagonal() => return Ok(line),
+                Some(_) => ParseErrorKind::UnsupportedAngle,
+                None => ParseErrorKind::Malformed,
+            };
+            Err(ParseError {
+                line: idx + 1,
+                text: text.to_string(),
+                kind,
+            })
+        })
+        .collect()
+}
+
+pub fn parse_input(raw: &str) -> Vec<Line> {
+    try_parse_input(raw, false).unwrap_or_else(|e| panic!("Expected input to parse, {}", e))
+}
+
+/// The lines that take part in the count, diagonal lines being excluded unless DIAG is set
+fn considered<const DIAG: bool>(lines: &[Line]) -> impl Iterator<Item = &Line> {
+    lines.iter().filter(|line| DIAG || !line.is_diagonal())
 }
 
-fn count_overlaps<const DIAG: bool>(lines: &[Line]) -> i32 {
+/// The original implementation, kept as a reference to compare the others against
+pub fn count_overlaps_hashmap<const DIAG: bool>(lines: &[Line], raster: Raster) -> i32 {
     let mut density_map = HashMap::<IntVec, i32>::new();
 
-    for line in lines.iter() {
-        if !DIAG {
-            let d = line.dir();
-            if d.x != 0 && d.y != 0 {
-                continue;
+    for line in considered::<DIAG>(lines) {
+        for point in line.points(raster) {
+            *density_map.entry(point).or_insert(0) += 1;
+        }
+    }
+
+    density_map.values().filter(|v| **v > 1).count() as i32
+}
+
+/// Counts overlaps using a dense grid covering the bounding box of the lines.
+///
+/// The caller must make sure the bounding box is small enough to allocate a byte per point, as
+/// `count_overlaps` does.
+pub fn count_overlaps_dense<const DIAG: bool>(lines: &[Line], raster: Raster) -> i32 {
+    let (min, max) = match bounding_box(considered::<DIAG>(lines)) {
+        Some(bounds) => bounds,
+        None => return 0,
+    };
+
+    let (width, height) = box_size(min, max);
+    let (width, height) = (width as usize, height as usize);
+
+    // Saturates at 2, as we only care whether a point has more than one line through it
+    let mut density_map = vec![0u8; width * height];
+    let mut overlaps = 0;
+
+    for line in considered::<DIAG>(lines) {
+        for point in line.points(raster) {
+            let idx = (point.x - min.x) as usize + (point.y - min.y) as usize * width;
+            let density = &mut density_map[idx];
+            if *density == 1 {
+                overlaps += 1;
+            }
+            if *density < 2 {
+                *density += 1;
             }
         }
+    }
 
-        for point in line.iter_points() {
-            *density_map.entry(point).or_insert(0) += 1;
+    overlaps
+}
+
+/// Counts overlaps a row at a time, only ever storing the lines crossing the current row.
+///
+/// Memory use is independent of the coordinate range, so this copes with lines spread sparsely
+/// over a large area.
+pub fn count_overlaps_sweep<const DIAG: bool>(lines: &[Line], raster: Raster) -> i32 {
+    let mut pending = considered::<DIAG>(lines).collect::<Vec<_>>();
+
+    // Sorted so that the lines starting on the lowest row are at the end
+    pending.sort_by_key(|line| std::cmp::Reverse(line.min_y()));
+
+    let mut active: Vec<&Line> = Vec::new();
+    let mut events = Vec::new();
+    let mut overlaps = 0;
+
+    let mut y = match pending.last() {
+        Some(line) => line.min_y(),
+        None => return 0,
+    };
+
+    loop {
+        active.retain(|line| line.max_y() >= y);
+        while pending.last().is_some_and(|line| line.min_y() == y) {
+            active.push(pending.pop().unwrap());
+        }
+
+        if active.is_empty() {
+            match pending.last() {
+                // Skip straight over any rows with no lines on
+                Some(line) => {
+                    y = line.min_y();
+                    continue;
+                }
+                None => break,
+            }
+        }
+
+        // +1 where a line's coverage of this row starts, -1 one past where it ends, which may be
+        // past the last i32
+        events.clear();
+        for line in active.iter() {
+            if let Some((start, end)) = line.x_span_at(y, raster) {
+                events.push((start as i64, 1));
+                events.push((end as i64 + 1, -1));
+            }
+        }
+        events.sort_unstable();
+
+        let mut depth = 0;
+        let mut prev_x = 0;
+        for (x, delta) in events.iter() {
+            if depth > 1 {
+                overlaps += (x - prev_x) as i32;
+            }
+            depth += delta;
+            prev_x = *x;
         }
+
+        y = match y.checked_add(1) {
+            Some(y) => y,
+            None => break,
+        };
     }
 
-    density_map.values().filter(|v| **v > 1).count() as i32
+    overlaps
+}
+
+fn bounding_box<'a>(lines: impl Iterator<Item = &'a Line>) -> Option<(IntVec, IntVec)> {
+    lines.fold(None, |bounds, line| {
+        let (mut min, mut max) = bounds.unwrap_or((line.start, line.start));
+        for p in [line.start, line.end] {
+            min.x = std::cmp::min(min.x, p.x);
+            min.y = std::cmp::min(min.y, p.y);
+            max.x = std::cmp::max(max.x, p.x);
+            max.y = std::cmp::max(max.y, p.y);
+        }
+        Some((min, max))
+    })
+}
+
+/// The width and height of the box between two corners, which can be more than an i32 can hold
+fn box_size(min: IntVec, max: IntVec) -> (i64, i64) {
+    (
+        max.x as i64 - min.x as i64 + 1,
+        max.y as i64 - min.y as i64 + 1,
+    )
+}
+
+/// Above this many cells in the bounding box, `count_overlaps` uses the sweep line instead of a
+/// dense grid.
+const DENSE_GRID_MAX_CELLS: i64 = 1 << 24;
+
+/// Counts the points covered by more than one line, picking a strategy based on how spread out
+/// the lines are.
+pub fn count_overlaps<const DIAG: bool>(lines: &[Line], raster: Raster) -> i32 {
+    let cells = match bounding_box(considered::<DIAG>(lines)) {
+        Some((min, max)) => {
+            let (width, height) = box_size(min, max);
+            width.saturating_mul(height)
+        }
+        None => return 0,
+    };
+
+    if cells <= DENSE_GRID_MAX_CELLS {
+        count_overlaps_dense::<DIAG>(lines, raster)
+    } else {
+        count_overlaps_sweep::<DIAG>(lines, raster)
+    }
+}
+
+/// A point with rational coordinates, `x / denom` and `y / denom`, kept in lowest terms with a
+/// positive denominator
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RationalPoint {
+    pub x: i64,
+    pub y: i64,
+    pub denom: i64,
+}
+
+impl RationalPoint {
+    fn new(x: i64, y: i64, denom: i64) -> Self {
+        let sign = denom.signum();
+        let divisor = gcd(gcd(x.abs(), y.abs()), denom.abs());
+        Self {
+            x: sign * x / divisor,
+            y: sign * y / divisor,
+            denom: sign * denom / divisor,
+        }
+    }
+
+    /// The lattice point at this position, if it is one
+    pub fn lattice(&self) -> Option<IntVec> {
+        (self.denom == 1).then_some(IntVec {
+            x: self.x as i32,
+            y: self.y as i32,
+        })
+    }
+}
+
+impl From<IntVec> for RationalPoint {
+    fn from(p: IntVec) -> Self {
+        Self {
+            x: p.x as i64,
+            y: p.y as i64,
+            denom: 1,
+        }
+    }
+}
+
+/// Where two lines meet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Intersection {
+    /// The lines cross or touch at a single point, which need not be a lattice point
+    Point(RationalPoint),
+
+    /// The lines are collinear and share this section, running in the direction of the first line
+    Overlap(Line),
+}
+
+impl Intersection {
+    /// The length of the shared section, zero for a single point
+    pub fn length(&self) -> f64 {
+        match self {
+            Intersection::Point(_) => 0.0,
+            Intersection::Overlap(line) => {
+                let d = line.delta();
+                (d.x as f64).hypot(d.y as f64)
+            }
+        }
+    }
+
+    /// The lattice points that both lines pass through
+    pub fn lattice_points(&self) -> Vec<IntVec> {
+        match self {
+            Intersection::Point(p) => p.lattice().into_iter().collect(),
+            Intersection::Overlap(line) => line.points(Raster::Exact).collect(),
+        }
+    }
+}
+
+impl Line {
+    /// The exact intersection of this line with another, if they meet at all
+    pub fn intersection(&self, other: &Line) -> Option<Intersection> {
+        let cross = |a: (i64, i64), b: (i64, i64)| a.0 * b.1 - a.1 * b.0;
+        let dot = |a: (i64, i64), b: (i64, i64)| a.0 * b.0 + a.1 * b.1;
+        let vec = |from: IntVec, to: IntVec| ((to.x - from.x) as i64, (to.y - from.y) as i64);
+
+        let p = self.start;
+        let r = vec(self.start, self.end);
+        let s = vec(other.start, other.end);
+        let pq = vec(p, other.start);
+
+        let denom = cross(r, s);
+        if denom != 0 {
+            // Solve p + t.r = q + u.s, with both t and u needing to be in [0, 1]
+            let (t, u) = (cross(pq, s), cross(pq, r));
+            let (t, u, denom) = if denom < 0 {
+                (-t, -u, -denom)
+            } else {
+                (t, u, denom)
+            };
+
+            if !(0..=denom).contains(&t) || !(0..=denom).contains(&u) {
+                return None;
+            }
+
+            return Some(Intersection::Point(RationalPoint::new(
+                p.x as i64 * denom + r.0 * t,
+                p.y as i64 * denom + r.1 * t,
+                denom,
+            )));
+        }
+
+        // Two single points have no direction to compare along, they only meet if they're equal
+        if r == (0, 0) && s == (0, 0) {
+            return (self.start == other.start).then_some(Intersection::Point(p.into()));
+        }
+
+        // Parallel, so they can only meet if they lie on the same infinite line
+        if cross(pq, r) != 0 || cross(pq, s) != 0 {
+            return None;
+        }
+
+        // Measure every end point along the shared direction, and find where the two ranges
+        // overlap. Each end of the overlap is always one of the original end points.
+        let axis = if r != (0, 0) { r } else { s };
+        let measure = |point: IntVec| dot(vec(p, point), axis);
+        let span = |line: &Line| {
+            let (a, b) = (measure(line.start), measure(line.end));
+            (std::cmp::min(a, b), std::cmp::max(a, b))
+        };
+
+        let (self_lo, self_hi) = span(self);
+        let (other_lo, other_hi) = span(other);
+        let (lo, hi) = (
+            std::cmp::max(self_lo, other_lo),
+            std::cmp::min(self_hi, other_hi),
+        );
+        if lo > hi {
+            return None;
+        }
+
+        let ends = [self.start, self.end, other.start, other.end];
+        let at = |m: i64| *ends.iter().find(|e| measure(**e) == m).unwrap();
+        if lo == hi {
+            Some(Intersection::Point(at(lo).into()))
+        } else if dot(axis, r) < 0 {
+            Some(Intersection::Overlap(Line {
+                start: at(hi),
+                end: at(lo),
+            }))
+        } else {
+            Some(Intersection::Overlap(Line {
+                start: at(lo),
+                end: at(hi),
+            }))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntersectingPair {
+    /// Indices of the two lines, the first always being the lower
+    pub first: usize,
+    pub second: usize,
+    pub intersection: Intersection,
+}
+
+impl IntersectingPair {
+    pub fn overlap_length(&self) -> f64 {
+        self.intersection.length()
+    }
+}
+
+/// Every pair of lines that meet, in order of the first and then the second line's index
+pub fn intersecting_pairs(lines: &[Line]) -> Vec<IntersectingPair> {
+    let mut pairs = Vec::new();
+    for (first, a) in lines.iter().enumerate() {
+        for (second, b) in lines.iter().enumerate().skip(first + 1) {
+            if let Some(intersection) = a.intersection(b) {
+                pairs.push(IntersectingPair {
+                    first,
+                    second,
+                    intersection,
+                });
+            }
+        }
+    }
+    pairs
+}
+
+/// The number of lines covering every point within the bounding box of a set of lines
+#[derive(Clone, Debug)]
+pub struct DensityMap {
+    min: IntVec,
+    width: usize,
+    height: usize,
+    counts: Vec<u32>,
+}
+
+/// Maps no larger than this in either dimension are rendered as text rather than as an image
+const ASCII_MAX_SIZE: usize = 80;
+
+/// The most cells `DensityMap::build` will allocate
+const DENSITY_MAP_MAX_CELLS: i64 = 1 << 24;
+
+/// The lines are spread over too large an area to build a density map of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MapTooLarge {
+    pub width: i64,
+    pub height: i64,
+}
+
+impl std::fmt::Display for MapTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "a {}x{} density map is larger than the limit of {} cells",
+            self.width, self.height, DENSITY_MAP_MAX_CELLS
+        )
+    }
+}
+
+impl std::error::Error for MapTooLarge {}
+
+impl DensityMap {
+    pub fn build(lines: &[Line], raster: Raster) -> Result<Self, MapTooLarge> {
+        let (min, max) = match bounding_box(lines.iter()) {
+            Some(bounds) => bounds,
+            None => {
+                return Ok(Self {
+                    min: IntVec { x: 0, y: 0 },
+                    width: 0,
+                    height: 0,
+                    counts: Vec::new(),
+                })
+            }
+        };
+
+        let (width, height) = box_size(min, max);
+        if width.saturating_mul(height) > DENSITY_MAP_MAX_CELLS {
+            return Err(MapTooLarge { width, height });
+        }
+
+        let (width, height) = (width as usize, height as usize);
+        let mut counts = vec![0; width * height];
+        for line in lines {
+            for point in line.points(raster) {
+                counts[(point.x - min.x) as usize + (point.y - min.y) as usize * width] += 1;
+            }
+        }
+
+        Ok(Self {
+            min,
+            width,
+            height,
+            counts,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The number of lines covering the given point
+    pub fn get(&self, x: i32, y: i32) -> u32 {
+        let (x, y) = (x - self.min.x, y - self.min.y);
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            0
+        } else {
+            self.counts[x as usize + y as usize * self.width]
+        }
+    }
+
+    pub fn max_count(&self) -> u32 {
+        self.counts.iter().cloned().max().unwrap_or(0)
+    }
+
+    /// The number of points covered by more than one line
+    pub fn overlaps(&self) -> usize {
+        self.counts.iter().filter(|c| **c > 1).count()
+    }
+
+    /// Writes the map as a binary greyscale PGM image, with the most covered points in white
+    pub fn write_pgm(&self, mut out: impl std::io::Write) -> std::io::Result<()> {
+        let max = std::cmp::max(self.max_count(), 1);
+        write!(out, "P5\n{} {}\n255\n", self.width, self.height)?;
+
+        let pixels = self
+            .counts
+            .iter()
+            .map(|c| (c * 255 / max) as u8)
+            .collect::<Vec<_>>();
+        out.write_all(&pixels)
+    }
+
+    /// Writes the map as text in the same style as the puzzle if it is small enough, otherwise as
+    /// a PGM image.
+    pub fn render(&self, mut out: impl std::io::Write) -> std::io::Result<()> {
+        if self.width <= ASCII_MAX_SIZE && self.height <= ASCII_MAX_SIZE {
+            write!(out, "{}", self)
+        } else {
+            self.write_pgm(out)
+        }
+    }
 }
 
-fn part_1(lines: &Vec<Line>) -> i32 {
-    count_overlaps::<false>(lines)
+impl std::fmt::Display for DensityMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.counts.chunks(self.width.max(1)) {
+            for count in row {
+                let c = match count {
+                    0 => '.',
+                    c => std::char::from_digit(*c, 10).unwrap_or('#'),
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
 }
 
-fn part_2(lines: &Vec<Line>) -> i32 {
-    count_overlaps::<true>(lines)
+pub fn part_1(lines: &[Line]) -> i32 {
+    count_overlaps::<false>(lines, Raster::Exact)
+}
+
+pub fn part_2(lines: &[Line]) -> i32 {
+    count_overlaps::<true>(lines, Raster::Exact)
 }
 
 impl_day!("2021", "5", "Hydrothermal Venture", Vec<Line>, i32, i32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_input() -> Vec<Line> {
+        parse_input(
+            "0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2",
+        )
+    }
+
+    fn assert_all_match(lines: &[Line]) {
+        for raster in [Raster::Exact, Raster::Bresenham] {
+            let expected = count_overlaps_hashmap::<false>(lines, raster);
+            assert_eq!(count_overlaps_dense::<false>(lines, raster), expected);
+            assert_eq!(count_overlaps_sweep::<false>(lines, raster), expected);
+            assert_eq!(count_overlaps::<false>(lines, raster), expected);
+
+            let expected = count_overlaps_hashmap::<true>(lines, raster);
+            assert_eq!(count_overlaps_dense::<true>(lines, raster), expected);
+            assert_eq!(count_overlaps_sweep::<true>(lines, raster), expected);
+            assert_eq!(count_overlaps::<true>(lines, raster), expected);
+        }
+    }
+
+    /// Lines at any angle, within a 50x50 square
+    fn random_lines(count: usize) -> Vec<Line> {
+        let mut rng = crate::Lcg::new(0x2545f491);
+        let mut next = move || (rng.next_u16() % 50) as i32;
+
+        (0..count)
+            .map(|_| Line {
+                start: IntVec {
+                    x: next(),
+                    y: next(),
+                },
+                end: IntVec {
+                    x: next(),
+                    y: next(),
+                },
+            })
+            .collect()
+    }
+
+    fn points(raw: &str, raster: Raster) -> Vec<(i32, i32)> {
+        try_parse_input(raw, true).unwrap()[0]
+            .points(raster)
+            .map(|p| (p.x, p.y))
+            .collect()
+    }
+
+    #[test]
+    fn test_example() {
+        let input = example_input();
+        assert_eq!(part_1(&input), 5);
+        assert_eq!(part_2(&input), 12);
+        assert_all_match(&input);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = try_parse_input("0,9 -> 5,9\n8,0 -> 0,7", false).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                line: 2,
+                text: "8,0 -> 0,7".to_string(),
+                kind: ParseErrorKind::UnsupportedAngle,
+            }
+        );
+        assert!(try_parse_input("0,9 -> 5,9\n8,0 -> 0,7", true).is_ok());
+
+        let kind = |s: &str| try_parse_input(s, true).unwrap_err().kind;
+        assert_eq!(kind("0,9 -> 5"), ParseErrorKind::Malformed);
+        assert_eq!(kind("0,9 5,9"), ParseErrorKind::Malformed);
+        assert_eq!(kind("a,9 -> 5,9"), ParseErrorKind::Malformed);
+    }
+
+    #[test]
+    fn test_raster() {
+        // Both rasters agree on the lines from the puzzle
+        for raw in ["0,9 -> 5,9", "7,4 -> 7,0", "8,0 -> 0,8", "3,3 -> 3,3"] {
+            assert_eq!(points(raw, Raster::Exact), points(raw, Raster::Bresenham));
+        }
+        assert_eq!(points("8,2 -> 5,5", Raster::Exact).len(), 4);
+
+        assert_eq!(
+            points("0,0 -> 6,4", Raster::Exact),
+            vec![(0, 0), (3, 2), (6, 4)]
+        );
+        assert_eq!(points("0,0 -> 5,3", Raster::Exact), vec![(0, 0), (5, 3)]);
+        assert_eq!(
+            points("0,0 -> 4,2", Raster::Bresenham),
+            vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]
+        );
+        assert_eq!(
+            points("1,4 -> 0,0", Raster::Bresenham),
+            vec![(1, 4), (1, 3), (0, 2), (0, 1), (0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_spans_match_points() {
+        for line in random_lines(200) {
+            for raster in [Raster::Exact, Raster::Bresenham] {
+                let points = line.points(raster).collect::<Vec<_>>();
+                for y in line.min_y()..=line.max_y() {
+                    let row = points.iter().filter(|p| p.y == y).map(|p| p.x);
+                    let expected = row.clone().min().map(|min| (min, row.max().unwrap()));
+                    assert_eq!(line.x_span_at(y, raster), expected, "{:?} {:?}", line, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_density_map() {
+        let map = DensityMap::build(&example_input(), Raster::Exact).unwrap();
+        assert_eq!((map.width(), map.height()), (10, 10));
+        assert_eq!(map.get(7, 1), 2);
+        assert_eq!(map.get(0, 0), 1);
+        assert_eq!(map.get(-1, 20), 0);
+        assert_eq!(map.max_count(), 3);
+        assert_eq!(map.overlaps(), 12);
+        assert_eq!(
+            map.to_string(),
+            "1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111....
+"
+        );
+
+        let mut rendered = Vec::new();
+        map.render(&mut rendered).unwrap();
+        assert_eq!(String::from_utf8(rendered).unwrap(), map.to_string());
+
+        let mut pgm = Vec::new();
+        map.write_pgm(&mut pgm).unwrap();
+        assert!(pgm.starts_with(b"P5\n10 10\n255\n"));
+        assert_eq!(pgm.len(), 13 + 100);
+        assert_eq!(pgm[13 + 7 + 10], 170);
+
+        let input = parse_input(include_str!("./input.txt"));
+        let map = DensityMap::build(&input, Raster::Exact).unwrap();
+        assert_eq!(map.overlaps(), part_2(&input) as usize);
+
+        let mut rendered = Vec::new();
+        map.render(&mut rendered).unwrap();
+        assert!(rendered.starts_with(b"P5\n"));
+
+        // Two short lines at opposite corners of the i32 range would need 2^64 cells
+        let sparse = [
+            line("-2147483648,-2147483648 -> -2147483648,-2147483647"),
+            line("2147483647,2147483647 -> 2147483647,2147483647"),
+        ];
+        assert_eq!(
+            DensityMap::build(&sparse, Raster::Exact).unwrap_err(),
+            MapTooLarge {
+                width: 1 << 32,
+                height: 1 << 32
+            }
+        );
+        assert!(DensityMap::build(&sparse[..1], Raster::Exact).is_ok());
+    }
+
+    fn line(raw: &str) -> Line {
+        try_parse_input(raw, true).unwrap()[0]
+    }
+
+    /// Counts the distinct lattice points shared by any pair of lines
+    fn count_shared_points(lines: &[Line]) -> i32 {
+        intersecting_pairs(lines)
+            .iter()
+            .flat_map(|pair| pair.intersection.lattice_points())
+            .collect::<std::collections::HashSet<_>>()
+            .len() as i32
+    }
+
+    #[test]
+    fn test_intersection() {
+        let point = |x, y, denom| Some(Intersection::Point(RationalPoint { x, y, denom }));
+        let overlap = |raw| Some(Intersection::Overlap(line(raw)));
+
+        // Crossing between lattice points, and exactly on one
+        assert_eq!(
+            line("0,0 -> 1,1").intersection(&line("0,1 -> 1,0")),
+            point(1, 1, 2)
+        );
+        assert_eq!(
+            line("0,0 -> 4,4").intersection(&line("0,4 -> 4,0")),
+            point(2, 2, 1)
+        );
+        assert_eq!(
+            line("0,0 -> 6,4").intersection(&line("0,3 -> 3,0")),
+            point(9, 6, 5)
+        );
+
+        // Touching at the ends, missing, and parallel
+        assert_eq!(
+            line("0,0 -> 2,0").intersection(&line("2,0 -> 2,5")),
+            point(2, 0, 1)
+        );
+        assert_eq!(line("0,0 -> 2,0").intersection(&line("3,0 -> 3,5")), None);
+        assert_eq!(line("0,0 -> 2,2").intersection(&line("1,0 -> 3,2")), None);
+
+        // Collinear
+        assert_eq!(
+            line("0,9 -> 5,9").intersection(&line("0,9 -> 2,9")),
+            overlap("0,9 -> 2,9")
+        );
+        assert_eq!(
+            line("9,4 -> 3,4").intersection(&line("3,4 -> 1,4")),
+            point(3, 4, 1)
+        );
+        assert_eq!(
+            line("8,8 -> 0,0").intersection(&line("2,2 -> 10,10")),
+            overlap("8,8 -> 2,2")
+        );
+        assert_eq!(line("0,0 -> 2,2").intersection(&line("3,3 -> 4,4")), None);
+
+        // Single points
+        assert_eq!(
+            line("3,3 -> 3,3").intersection(&line("0,0 -> 4,4")),
+            point(3, 3, 1)
+        );
+        assert_eq!(line("3,3 -> 3,3").intersection(&line("0,1 -> 4,5")), None);
+        assert_eq!(line("3,3 -> 3,3").intersection(&line("5,9 -> 5,9")), None);
+        assert_eq!(
+            line("3,3 -> 3,3").intersection(&line("3,3 -> 3,3")),
+            point(3, 3, 1)
+        );
+
+        let points = [line("3,3 -> 3,3"), line("5,9 -> 5,9")];
+        assert_eq!(intersecting_pairs(&points), vec![]);
+        assert_eq!(count_overlaps::<true>(&points, Raster::Exact), 0);
+    }
+
+    #[test]
+    fn test_intersecting_pairs() {
+        let input = example_input();
+        let pairs = intersecting_pairs(&input);
+        assert_eq!(
+            pairs[0],
+            IntersectingPair {
+                first: 0,
+                second: 6,
+                intersection: Intersection::Overlap(line("0,9 -> 2,9")),
+            }
+        );
+        assert_eq!(pairs[0].overlap_length(), 2.0);
+        assert!(pairs.iter().all(|pair| pair.first < pair.second));
+
+        assert_eq!(count_shared_points(&input), part_2(&input));
+
+        let straight = input
+            .iter()
+            .filter(|line| !line.is_diagonal())
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(count_shared_points(&straight), part_1(&input));
+
+        let input = parse_input(include_str!("./input.txt"));
+        assert_eq!(count_shared_points(&input), part_2(&input));
+
+        let random = random_lines(200);
+        assert_eq!(
+            count_shared_points(&random),
+            count_overlaps::<true>(&random, Raster::Exact)
+        );
+    }
+
+    #[test]
+    fn test_strategies_match() {
+        assert_all_match(&parse_input(include_str!("./input.txt")));
+        assert_all_match(&[]);
+        assert_all_match(&random_lines(200));
+
+        // The example spread out with large gaps between each line, which is too big for the
+        // dense grid to be chosen automatically.
+        let spread = example_input()
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let offset = IntVec {
+                    x: 1_000_000 * (i as i32 % 3),
+                    y: 500_000 * (i as i32 / 3),
+                };
+                Line {
+                    start: line.start + offset,
+                    end: line.end + offset,
+                }
+            })
+            .chain(example_input())
+            .collect::<Vec<_>>();
+
+        let expected = count_overlaps_hashmap::<true>(&spread, Raster::Exact);
+        assert_eq!(
+            count_overlaps_sweep::<true>(&spread, Raster::Exact),
+            expected
+        );
+        assert_eq!(count_overlaps::<true>(&spread, Raster::Exact), expected);
+        assert_eq!(
+            count_overlaps::<false>(&spread, Raster::Exact),
+            count_overlaps_hashmap::<false>(&spread, Raster::Exact)
+        );
+
+        // A bounding box wider and taller than an i32 can hold, and lines reaching its edges
+        let extremes = [
+            (i32::MIN, 0, i32::MIN, 5),
+            (i32::MIN, 3, i32::MIN, 8),
+            (i32::MAX, 0, i32::MAX, 5),
+            (i32::MAX - 5, i32::MAX, i32::MAX, i32::MAX),
+            (i32::MAX - 2, i32::MAX, i32::MAX, i32::MAX),
+            (0, i32::MIN, 0, i32::MIN + 1),
+        ]
+        .map(|(x1, y1, x2, y2)| Line {
+            start: IntVec { x: x1, y: y1 },
+            end: IntVec { x: x2, y: y2 },
+        });
+        for raster in [Raster::Exact, Raster::Bresenham] {
+            assert_eq!(count_overlaps_hashmap::<true>(&extremes, raster), 6);
+            assert_eq!(count_overlaps_sweep::<true>(&extremes, raster), 6);
+            assert_eq!(count_overlaps::<true>(&extremes, raster), 6);
+        }
+    }
+}
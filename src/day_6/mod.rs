@@ -1,26 +1,216 @@
-#[derive(Clone, Copy)]
+use num_traits::{CheckedAdd, CheckedMul, One, Zero};
+
+/// A type the fish population can be counted in, such as `u128` or `num_bigint::BigUint`
+pub trait Count: Clone + Zero + One + CheckedAdd + CheckedMul + From<u64> {}
+
+impl<T: Clone + Zero + One + CheckedAdd + CheckedMul + From<u64>> Count for T {}
+
+/// A square matrix, stored row-major
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Matrix<T> {
+    size: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Count> Matrix<T> {
+    fn identity(size: usize) -> Self {
+        let mut cells = vec![T::zero(); size * size];
+        for i in 0..size {
+            cells[i * size + i] = T::one();
+        }
+        Self { size, cells }
+    }
+
+    fn get(&self, row: usize, col: usize) -> &T {
+        &self.cells[row * self.size + col]
+    }
+
+    /// Returns None if any element overflows
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for row in 0..self.size {
+            for col in 0..self.size {
+                let mut sum = T::zero();
+                for k in 0..self.size {
+                    sum = sum.checked_add(&self.get(row, k).checked_mul(rhs.get(k, col))?)?;
+                }
+                cells.push(sum);
+            }
+        }
+
+        Some(Self {
+            size: self.size,
+            cells,
+        })
+    }
+
+    fn checked_pow(&self, mut n: u64) -> Option<Self> {
+        let mut result = Self::identity(self.size);
+        let mut base = self.clone();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.checked_mul(&base)?;
+            }
+            n >>= 1;
+
+            // Skipping the final squaring means every power computed is at most the one asked for
+            if n > 0 {
+                base = base.checked_mul(&base)?;
+            }
+        }
+        Some(result)
+    }
+
+    fn checked_apply(&self, v: &[T]) -> Option<Vec<T>> {
+        (0..self.size)
+            .map(|row| {
+                v.iter().enumerate().try_fold(T::zero(), |sum, (col, x)| {
+                    sum.checked_add(&self.get(row, col).checked_mul(x)?)
+                })
+            })
+            .collect()
+    }
+}
+
+/// How a species of fish ages and reproduces.
+///
+/// The fields are only set through `new`, so the spawn interval is always at least 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lifecycle {
+    spawn_interval: usize,
+    newborn_delay: usize,
+}
+
+impl Lifecycle {
+    pub const LANTERNFISH: Self = Self {
+        spawn_interval: 7,
+        newborn_delay: 2,
+    };
+
+    pub fn new(spawn_interval: usize, newborn_delay: usize) -> Result<Self, LifecycleError> {
+        if spawn_interval == 0 {
+            return Err(LifecycleError::ZeroSpawnInterval);
+        }
+
+        Ok(Self {
+            spawn_interval,
+            newborn_delay,
+        })
+    }
+
+    /// The number of days between a fish spawning and spawning again
+    pub fn spawn_interval(&self) -> usize {
+        self.spawn_interval
+    }
+
+    /// The extra days a newborn fish takes before its first spawn cycle
+    pub fn newborn_delay(&self) -> usize {
+        self.newborn_delay
+    }
+
+    /// One more than the largest timer value, that of a newborn fish
+    fn ring_size(&self) -> usize {
+        self.spawn_interval + self.newborn_delay
+    }
+
+    /// The factor the population grows by each day in the long run.
+    ///
+    /// The number of fish spawning on day t is the sum of those spawning on days t - interval and
+    /// t - (interval + delay), so this is the largest real root of x^n = x^(n - interval) + 1,
+    /// where n is the interval plus the delay. It always lies between 1 and 2.
+    pub fn growth_rate(&self) -> f64 {
+        let n = self.ring_size() as i32;
+        let f = |x: f64| x.powi(n) - x.powi(n - self.spawn_interval as i32) - 1.0;
+
+        let (mut low, mut high) = (1.0, 2.0);
+        for _ in 0..64 {
+            let mid = (low + high) / 2.0;
+            if f(mid) < 0.0 {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LifecycleError {
+    ZeroSpawnInterval,
+    TimerOutOfRange {
+        timer: usize,
+        max: usize,
+    },
+
+    /// An entry in the input that isn't a timer value
+    InvalidTimer(String),
+}
+
+impl std::fmt::Display for LifecycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LifecycleError::ZeroSpawnInterval => write!(f, "spawn interval must be at least 1"),
+            LifecycleError::TimerOutOfRange { timer, max } => {
+                write!(f, "timer {} is greater than the maximum of {}", timer, max)
+            }
+            LifecycleError::InvalidTimer(raw) => write!(f, "{:?} is not a valid timer", raw),
+        }
+    }
+}
+
+impl std::error::Error for LifecycleError {}
+
+#[derive(Clone, Debug)]
 pub struct FishState {
-    // Fixed size ring buffer
-    counts: [u64; 9],
+    lifecycle: Lifecycle,
+
+    // Fixed size ring buffer, with one slot per timer value
+    counts: Vec<u64>,
 
     // The index of the first element of the ring
     head: usize,
 }
 
 impl FishState {
+    /// A population with one fish for each of the given timer values
+    pub fn new(
+        lifecycle: Lifecycle,
+        timers: impl IntoIterator<Item = usize>,
+    ) -> Result<Self, LifecycleError> {
+        let mut counts = vec![0; lifecycle.ring_size()];
+        for timer in timers {
+            match counts.get_mut(timer) {
+                Some(count) => *count += 1,
+                None => {
+                    return Err(LifecycleError::TimerOutOfRange {
+                        timer,
+                        max: lifecycle.ring_size() - 1,
+                    })
+                }
+            }
+        }
+
+        Ok(Self {
+            lifecycle,
+            counts,
+            head: 0,
+        })
+    }
+
     fn step(&mut self) {
         // Pop the first element of the ring buffer
         // SAFETY: The head index is private, so we can ensure it is always in bounds.
         let spawning_count = unsafe { *self.counts.get_unchecked(self.head) };
         self.head = (self.head + 1) % self.counts.len();
 
-        // Set the last element of the ring to the popped value
-        let tail_8 = (self.head + 8) % self.counts.len();
-        self.counts[tail_8] = spawning_count;
+        // Set the last element of the ring (the newborns) to the popped value
+        let tail_newborn = (self.head + self.counts.len() - 1) % self.counts.len();
+        self.counts[tail_newborn] = spawning_count;
 
-        // Increment the 6th value of the ring by the popped value
-        let tail_6 = (self.head + 6) % self.counts.len();
-        self.counts[tail_6] += spawning_count;
+        // Increment the value of the ring for a fish that has just spawned by the popped value
+        let tail_reset = (self.head + self.lifecycle.spawn_interval - 1) % self.counts.len();
+        self.counts[tail_reset] += spawning_count;
     }
 
     fn step_n(&mut self, n: u64) {
@@ -32,18 +222,198 @@ impl FishState {
     fn total_count(&self) -> u64 {
         self.counts.iter().sum()
     }
+
+    /// The number of fish with each timer value, starting from 0
+    pub fn timer_counts(&self) -> Vec<u64> {
+        (0..self.counts.len())
+            .map(|timer| self.counts[(self.head + timer) % self.counts.len()])
+            .collect()
+    }
+
+    /// The matrix taking one day's timer counts to the next day's
+    fn transition<T: Count>(&self) -> Matrix<T> {
+        let size = self.counts.len();
+        let mut matrix = Matrix {
+            size,
+            cells: vec![T::zero(); size * size],
+        };
+
+        // With no newborn delay, both of these land on the same element
+        let mut add = |row: usize, col: usize| {
+            let cell = &mut matrix.cells[row * size + col];
+            *cell = cell.clone() + T::one();
+        };
+
+        for timer in 1..size {
+            add(timer - 1, timer);
+        }
+        add(self.lifecycle.spawn_interval - 1, 0);
+        add(size - 1, 0);
+
+        matrix
+    }
+
+    /// The population on every day from today up to and including `days` days from now, counted
+    /// in `T`
+    pub fn time_series<T: Count>(&self, days: u64) -> TimeSeries<T> {
+        TimeSeries {
+            lifecycle: self.lifecycle,
+            counts: self.timer_counts().into_iter().map(T::from).collect(),
+            head: 0,
+            day: 0,
+            last_day: Some(days),
+        }
+    }
+
+    /// The total number of fish after the given number of days, in time logarithmic in `days`.
+    ///
+    /// Returns None if the count overflows `T`. As the intermediate products can slightly exceed
+    /// the final count, this may also happen for counts within a small factor of `T`'s limit.
+    pub fn count_after<T: Count>(&self, days: u64) -> Option<T> {
+        let counts = self
+            .timer_counts()
+            .into_iter()
+            .map(T::from)
+            .collect::<Vec<_>>();
+        self.transition::<T>()
+            .checked_pow(days)?
+            .checked_apply(&counts)?
+            .iter()
+            .try_fold(T::zero(), |sum, x| sum.checked_add(x))
+    }
 }
 
-fn parse_input(raw: &str) -> FishState {
-    let mut state = FishState {
-        counts: [0; 9],
-        head: 0,
-    };
-    for age in raw.split(",").map(|s| s.parse::<usize>().unwrap()) {
-        state.counts[age] += 1;
+/// The population on a single day of a `TimeSeries`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PopulationSample<T = u64> {
+    pub day: u64,
+    pub total: T,
+
+    /// The number of fish with each timer value, starting from 0
+    pub timers: Vec<T>,
+}
+
+/// Iterator over the population each day, which ends early if the population overflows `T`
+pub struct TimeSeries<T = u64> {
+    lifecycle: Lifecycle,
+
+    // The same ring buffer as `FishState`, in the series' own count type
+    counts: Vec<T>,
+    head: usize,
+
+    day: u64,
+
+    // None once the series has finished
+    last_day: Option<u64>,
+}
+
+impl<T: Count> TimeSeries<T> {
+    /// Like `FishState::step`, but leaves the counts untouched and returns false if any would
+    /// overflow
+    fn checked_step(&mut self) -> bool {
+        let len = self.counts.len();
+        let spawning_count = self.counts[self.head].clone();
+        let head = (self.head + 1) % len;
+        let newborn = (head + len - 1) % len;
+        let reset = (head + self.lifecycle.spawn_interval - 1) % len;
+
+        // With no newborn delay, the reset slot is the newborn slot, which is overwritten first
+        let existing = if reset == newborn {
+            &spawning_count
+        } else {
+            &self.counts[reset]
+        };
+        let reset_count = match existing.checked_add(&spawning_count) {
+            Some(count) => count,
+            None => return false,
+        };
+
+        self.head = head;
+        self.counts[newborn] = spawning_count;
+        self.counts[reset] = reset_count;
+        true
     }
+}
 
-    state
+impl<T: Count> Iterator for TimeSeries<T> {
+    type Item = PopulationSample<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let last_day = self.last_day?;
+
+        let timers = (0..self.counts.len())
+            .map(|timer| self.counts[(self.head + timer) % self.counts.len()].clone())
+            .collect::<Vec<_>>();
+        let total = timers
+            .iter()
+            .try_fold(T::zero(), |sum, count| sum.checked_add(count));
+        let total = match total {
+            Some(total) => total,
+            None => {
+                self.last_day = None;
+                return None;
+            }
+        };
+
+        let sample = PopulationSample {
+            day: self.day,
+            total,
+            timers,
+        };
+
+        if self.day == last_day || !self.checked_step() {
+            self.last_day = None;
+        }
+        self.day += 1;
+
+        Some(sample)
+    }
+}
+
+/// Writes the population for each day as CSV, with a column for the total and one for each timer
+/// value.
+///
+/// Returns the last day written, which is earlier than asked for if the population overflowed.
+pub fn write_time_series_csv<T: Count + std::fmt::Display>(
+    series: TimeSeries<T>,
+    mut out: impl std::io::Write,
+) -> std::io::Result<Option<u64>> {
+    let timers = series.counts.len();
+    write!(out, "day,total")?;
+    for timer in 0..timers {
+        write!(out, ",timer_{}", timer)?;
+    }
+    writeln!(out)?;
+
+    let mut last_day = None;
+    for sample in series {
+        last_day = Some(sample.day);
+        write!(out, "{},{}", sample.day, sample.total)?;
+        for count in sample.timers {
+            write!(out, ",{}", count)?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(last_day)
+}
+
+pub fn parse_with_lifecycle(raw: &str, lifecycle: Lifecycle) -> Result<FishState, LifecycleError> {
+    let timers = raw
+        .trim()
+        .split(",")
+        .map(|s| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| LifecycleError::InvalidTimer(s.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    FishState::new(lifecycle, timers)
+}
+
+pub fn parse_input(raw: &str) -> FishState {
+    parse_with_lifecycle(raw, Lifecycle::LANTERNFISH).unwrap()
 }
 
 fn part_1(input: &FishState) -> u64 {
@@ -59,3 +429,227 @@ fn part_2(input: &FishState) -> u64 {
 }
 
 impl_day!("2021", "6", "Laternfish", FishState, u64, u64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+
+    fn example_input() -> FishState {
+        parse_input("3,4,3,1,2")
+    }
+
+    #[test]
+    fn test_example() {
+        let input = example_input();
+        assert_eq!(part_1(&input), 5934);
+        assert_eq!(part_2(&input), 26984457539);
+    }
+
+    #[test]
+    fn test_count_after() {
+        let input = example_input();
+        assert_eq!(input.count_after::<u64>(18), Some(26));
+        assert_eq!(input.count_after::<u64>(80), Some(5934));
+        assert_eq!(input.count_after::<u128>(256), Some(26984457539));
+        assert_eq!(input.count_after::<u64>(0), Some(5));
+
+        let real = parse_input(include_str!("./input.txt"));
+        for days in [0, 1, 7, 80, 100, 256] {
+            let mut stepped = real.clone();
+            stepped.step_n(days);
+            assert_eq!(real.count_after::<u64>(days), Some(stepped.total_count()));
+            assert_eq!(
+                real.count_after::<BigUint>(days),
+                Some(BigUint::from(stepped.total_count()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_after_overflow() {
+        let input = example_input();
+        assert_eq!(input.count_after::<u64>(10_000), None);
+        assert_eq!(input.count_after::<u128>(10_000), None);
+
+        let big = input.count_after::<BigUint>(10_000).unwrap();
+        assert_eq!(big.to_string().len(), 380);
+        assert!(big.to_string().starts_with("12295220913725202160"));
+
+        // Both the u128 and arbitrary precision paths agree right up until the u128 overflows
+        let last_u128 = (800..1200)
+            .take_while(|days| input.count_after::<u128>(*days).is_some())
+            .last()
+            .unwrap();
+        assert_eq!(last_u128, 998);
+        assert_eq!(
+            input.count_after::<u128>(last_u128).map(BigUint::from),
+            input.count_after::<BigUint>(last_u128)
+        );
+    }
+
+    #[test]
+    fn test_time_series() {
+        let input = example_input();
+        let series = input.time_series::<u64>(18).collect::<Vec<_>>();
+        assert_eq!(series.len(), 19);
+        assert_eq!(
+            series[0],
+            PopulationSample {
+                day: 0,
+                total: 5,
+                timers: vec![0, 1, 1, 2, 1, 0, 0, 0, 0],
+            }
+        );
+        assert_eq!(series[18].total, 26);
+        assert_eq!(series[18].timers, vec![3, 5, 3, 2, 2, 1, 5, 1, 4]);
+        for (day, sample) in series.iter().enumerate() {
+            assert_eq!(sample.day, day as u64);
+            assert_eq!(input.count_after::<u64>(day as u64), Some(sample.total));
+        }
+
+        // Stops at the last day that fits in a u64
+        let series = input.time_series::<u64>(10_000).collect::<Vec<_>>();
+        let last = series.last().unwrap();
+        assert!(last.day < 10_000);
+        assert_eq!(input.count_after::<u64>(last.day), Some(last.total));
+        assert_eq!(input.count_after::<u64>(last.day + 1), None);
+
+        // Arbitrary precision carries on past it
+        let series = input.time_series::<BigUint>(600).collect::<Vec<_>>();
+        assert_eq!(series.len(), 601);
+        assert_eq!(series[last.day as usize].total, BigUint::from(last.total));
+        assert_eq!(
+            Some(series[600].total.clone()),
+            input.count_after::<BigUint>(600)
+        );
+    }
+
+    #[test]
+    fn test_growth_rate() {
+        for (interval, delay) in [(7, 2), (1, 0), (3, 0), (4, 1), (10, 5)] {
+            let lifecycle = Lifecycle::new(interval, delay).unwrap();
+            let state = FishState::new(lifecycle, [0]).unwrap();
+
+            // By the end of the series the growth has settled on the closed form rate. Measured
+            // over a whole cycle, as with a single starting fish some lifecycles only ever have
+            // fish spawning on some days of the cycle.
+            let series = state.time_series::<u64>(1000).collect::<Vec<_>>();
+            let cycle = interval + delay;
+            let before = &series[series.len() - 1 - cycle];
+            let after = &series[series.len() - 1];
+            let rate = (after.total as f64 / before.total as f64).powf(1.0 / cycle as f64);
+            assert!(
+                (rate - lifecycle.growth_rate()).abs() < 1e-4,
+                "{:?} {} {}",
+                lifecycle,
+                rate,
+                lifecycle.growth_rate()
+            );
+        }
+
+        assert!((Lifecycle::LANTERNFISH.growth_rate() - 1.0910244).abs() < 1e-7);
+        assert!((Lifecycle::new(1, 0).unwrap().growth_rate() - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_time_series_csv() {
+        let mut csv = Vec::new();
+        let last_day =
+            write_time_series_csv(example_input().time_series::<u64>(2), &mut csv).unwrap();
+        assert_eq!(last_day, Some(2));
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "day,total,timer_0,timer_1,timer_2,timer_3,timer_4,timer_5,timer_6,timer_7,timer_8
+0,5,0,1,1,2,1,0,0,0,0
+1,5,1,1,2,1,0,0,0,0,0
+2,6,1,2,1,0,0,0,1,0,1
+"
+        );
+
+        // Doubling every day, the population of 2^64 on day 64 doesn't fit
+        let doubling = FishState::new(Lifecycle::new(1, 0).unwrap(), [0]).unwrap();
+        let last_day =
+            write_time_series_csv(doubling.time_series::<u64>(100), std::io::sink()).unwrap();
+        assert_eq!(last_day, Some(63));
+        let last_day =
+            write_time_series_csv(doubling.time_series::<BigUint>(100), std::io::sink()).unwrap();
+        assert_eq!(last_day, Some(100));
+    }
+
+    /// Simulates every fish individually
+    fn naive_count(lifecycle: Lifecycle, timers: &[usize], days: u64) -> u64 {
+        let mut fish = timers.to_vec();
+        for _ in 0..days {
+            let mut newborns = 0;
+            for timer in fish.iter_mut() {
+                if *timer == 0 {
+                    *timer = lifecycle.spawn_interval() - 1;
+                    newborns += 1;
+                } else {
+                    *timer -= 1;
+                }
+            }
+            fish.resize(fish.len() + newborns, lifecycle.ring_size() - 1);
+        }
+        fish.len() as u64
+    }
+
+    #[test]
+    fn test_default_lifecycle() {
+        let real = parse_input(include_str!("./input.txt"));
+        assert_eq!(part_1(&real), 380243);
+        assert_eq!(part_2(&real), 1708791884591);
+
+        let explicit = parse_with_lifecycle("3,4,3,1,2", Lifecycle::new(7, 2).unwrap()).unwrap();
+        assert_eq!(part_2(&explicit), part_2(&example_input()));
+    }
+
+    #[test]
+    fn test_custom_lifecycles() {
+        for (interval, delay) in [(7, 2), (1, 0), (1, 3), (3, 0), (4, 1), (10, 5)] {
+            let lifecycle = Lifecycle::new(interval, delay).unwrap();
+            let timers = [3, 4, 3, 1, 2, 0].map(|t| t % lifecycle.ring_size());
+            let state = FishState::new(lifecycle, timers).unwrap();
+            for days in [0, 1, 5, 12, 16] {
+                let expected = naive_count(lifecycle, &timers, days);
+
+                let mut stepped = state.clone();
+                stepped.step_n(days);
+                assert_eq!(stepped.total_count(), expected);
+                assert_eq!(state.count_after::<u64>(days), Some(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lifecycle_validation() {
+        assert_eq!(Lifecycle::new(0, 2), Err(LifecycleError::ZeroSpawnInterval));
+
+        let short = Lifecycle::new(3, 1).unwrap();
+        assert_eq!(
+            parse_with_lifecycle("3,4,3,1,2", short).unwrap_err(),
+            LifecycleError::TimerOutOfRange { timer: 4, max: 3 }
+        );
+        assert_eq!(
+            LifecycleError::TimerOutOfRange { timer: 4, max: 3 }.to_string(),
+            "timer 4 is greater than the maximum of 3"
+        );
+
+        assert_eq!(
+            parse_with_lifecycle("3,4,x,1", Lifecycle::LANTERNFISH).unwrap_err(),
+            LifecycleError::InvalidTimer("x".to_string())
+        );
+        assert_eq!(
+            parse_with_lifecycle("3,-1", Lifecycle::LANTERNFISH).unwrap_err(),
+            LifecycleError::InvalidTimer("-1".to_string())
+        );
+        assert_eq!(
+            LifecycleError::InvalidTimer("x".to_string()).to_string(),
+            "\"x\" is not a valid timer"
+        );
+
+        assert_eq!(short.spawn_interval(), 3);
+        assert_eq!(short.newborn_delay(), 1);
+    }
+}
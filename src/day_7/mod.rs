@@ -1,30 +1,277 @@
-fn parse_input(raw: &str) -> Vec<i32> {
-    raw.split(",").map(str::parse).map(Result::unwrap).collect()
+pub fn parse_input(raw: &str) -> Vec<i32> {
+    raw.trim()
+        .split(",")
+        .map(str::parse)
+        .map(Result::unwrap)
+        .collect()
 }
 
-fn part_1(input: &[i32]) -> i32 {
-    let min = *input.iter().min().unwrap();
-    let max = *input.iter().max().unwrap();
+/// A position for the crabs to line up on, and the total fuel it takes them to get there
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Alignment {
+    pub position: i32,
+    pub cost: i64,
+}
+
+/// How much fuel a crab's engine burns to move a given number of steps
+pub trait FuelCost {
+    fn cost(&self, distance: i64) -> i64;
+
+    /// Whether the cost is convex and non-decreasing in the distance. If so, the total cost is
+    /// convex in the position the crabs align on, so `minimise` can search for the cheapest
+    /// position rather than trying them all.
+    fn is_convex(&self) -> bool {
+        false
+    }
+}
+
+/// Every step costs one unit of fuel
+#[derive(Clone, Copy, Debug)]
+pub struct Linear;
+
+/// Each step costs one more unit of fuel than the last
+#[derive(Clone, Copy, Debug)]
+pub struct Triangular;
+
+/// The cost is the square of the distance
+#[derive(Clone, Copy, Debug)]
+pub struct Quadratic;
+
+/// Wraps a cost function that is known to be convex and non-decreasing
+#[derive(Clone, Copy, Debug)]
+pub struct Convex<F>(pub F);
+
+impl FuelCost for Linear {
+    fn cost(&self, distance: i64) -> i64 {
+        distance
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: i64) -> i64 {
+        (distance * (distance + 1)) / 2
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
 
-    (min..max)
-        .map(|a| input.iter().map(|b| (a - b).abs()).sum())
-        .min()
-        .unwrap()
+impl FuelCost for Quadratic {
+    fn cost(&self, distance: i64) -> i64 {
+        distance * distance
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
 }
 
-fn part_2(input: &[i32]) -> i32 {
-    let min = *input.iter().min().unwrap();
-    let max = *input.iter().max().unwrap();
+impl<F: Fn(i64) -> i64> FuelCost for Convex<F> {
+    fn cost(&self, distance: i64) -> i64 {
+        (self.0)(distance)
+    }
 
-    fn fuel_req(a: i32, b: i32) -> i32 {
-        let n = (a - b).abs();
-        (n * (n + 1)) / 2
+    fn is_convex(&self) -> bool {
+        true
     }
+}
 
-    (min..max)
-        .map(|a| input.iter().map(|b| fuel_req(a, *b)).sum())
-        .min()
-        .unwrap()
+/// Any other cost function, with no assumptions made about its shape
+impl<F: Fn(i64) -> i64> FuelCost for F {
+    fn cost(&self, distance: i64) -> i64 {
+        self(distance)
+    }
 }
 
-impl_day!("2021", "7", "The Treachery of Whales", Vec<i32>, i32, i32);
+fn alignment_at(input: &[i32], position: i32, cost: &impl FuelCost) -> Alignment {
+    Alignment {
+        position,
+        cost: input
+            .iter()
+            .map(|b| cost.cost((position as i64 - *b as i64).abs()))
+            .sum(),
+    }
+}
+
+/// The cheapest of the candidate positions, preferring the lowest position if there is a tie
+fn cheapest(
+    input: &[i32],
+    candidates: impl Iterator<Item = i32>,
+    cost: &impl FuelCost,
+) -> Option<Alignment> {
+    candidates
+        .map(|position| alignment_at(input, position, cost))
+        .min_by_key(|alignment| (alignment.cost, alignment.position))
+}
+
+/// Tries every position between the outermost crabs
+pub fn brute_force(input: &[i32], cost: &impl FuelCost) -> Option<Alignment> {
+    let min = *input.iter().min()?;
+    let max = *input.iter().max()?;
+    cheapest(input, min..=max, cost)
+}
+
+/// Finds the cheapest position, preferring the lowest if there is a tie.
+///
+/// Convex costs are minimised with a binary search for the first position where moving one step
+/// further doesn't reduce the total, any other costs try every position.
+pub fn minimise(input: &[i32], cost: &impl FuelCost) -> Option<Alignment> {
+    if !cost.is_convex() {
+        return brute_force(input, cost);
+    }
+
+    let mut low = *input.iter().min()?;
+    let mut high = *input.iter().max()?;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if alignment_at(input, mid, cost).cost <= alignment_at(input, mid + 1, cost).cost {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    Some(alignment_at(input, low, cost))
+}
+
+/// With a constant cost per step, the median position is optimal
+pub fn align_linear(input: &[i32]) -> Option<Alignment> {
+    let mut sorted = input.to_vec();
+    sorted.sort_unstable();
+
+    // With an even number of crabs anywhere between the two middle crabs is equally good, so take
+    // the lower to match `brute_force`
+    let median = *sorted.get(sorted.len().checked_sub(1)? / 2)?;
+    Some(alignment_at(input, median, &Linear))
+}
+
+/// With the cost growing by one for each step, the optimum is within half a step of the mean
+pub fn align_triangular(input: &[i32]) -> Option<Alignment> {
+    let min = *input.iter().min()?;
+    let max = *input.iter().max()?;
+    let sum = input.iter().map(|x| *x as i64).sum::<i64>();
+    let mean = sum.div_euclid(input.len() as i64) as i32;
+
+    let low = std::cmp::max(mean - 1, min);
+    let high = std::cmp::min(mean + 1, max);
+    cheapest(input, low..=high, &Triangular)
+}
+
+fn part_1(input: &[i32]) -> i64 {
+    align_linear(input).unwrap().cost
+}
+
+fn part_2(input: &[i32]) -> i64 {
+    align_triangular(input).unwrap().cost
+}
+
+impl_day!("2021", "7", "The Treachery of Whales", Vec<i32>, i64, i64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_input() -> Vec<i32> {
+        parse_input("16,1,2,0,4,2,7,1,2,14")
+    }
+
+    /// Crab positions spread over a range that varies from input to input
+    fn random_inputs() -> impl Iterator<Item = Vec<i32>> {
+        let mut rng = crate::Lcg::new(0x9e3779b9);
+
+        (0..200).map(move |_| {
+            let len = 1 + rng.next_u16() as usize % 40;
+            let range = 1 + rng.next_u16() as i32 % 2000;
+            let offset = rng.next_u16() as i32 % 1000 - 500;
+            (0..len)
+                .map(|_| rng.next_u16() as i32 % range + offset)
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_example() {
+        let input = example_input();
+        assert_eq!(
+            align_linear(&input),
+            Some(Alignment {
+                position: 2,
+                cost: 37
+            })
+        );
+        assert_eq!(
+            align_triangular(&input),
+            Some(Alignment {
+                position: 5,
+                cost: 168
+            })
+        );
+        assert_eq!(part_1(&input), 37);
+        assert_eq!(part_2(&input), 168);
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        for input in random_inputs() {
+            assert_eq!(
+                align_linear(&input),
+                brute_force(&input, &Linear),
+                "{:?}",
+                input
+            );
+            assert_eq!(
+                align_triangular(&input),
+                brute_force(&input, &Triangular),
+                "{:?}",
+                input
+            );
+        }
+
+        // The old range excluded the furthest crab, which matters when it is the optimum
+        assert_eq!(align_linear(&[0, 5, 5]).unwrap().position, 5);
+        assert_eq!(brute_force(&[0, 5, 5], &Linear).unwrap().position, 5);
+
+        assert_eq!(align_linear(&[]), None);
+        assert_eq!(align_triangular(&[]), None);
+    }
+
+    #[test]
+    fn test_minimise() {
+        let input = example_input();
+        assert_eq!(minimise(&input, &Linear), align_linear(&input));
+        assert_eq!(minimise(&input, &Triangular), align_triangular(&input));
+
+        // Closures are brute forced unless known to be convex
+        let cube = |d: i64| d * d * d;
+        assert!(!cube.is_convex());
+        assert_eq!(minimise(&input, &cube), brute_force(&input, &cube));
+        assert_eq!(minimise(&input, &Convex(cube)), brute_force(&input, &cube));
+
+        // Not convex, so the binary search could get stuck on the wrong side of a bump
+        let bumpy = |d: i64| if d % 2 == 0 { d } else { d * 3 };
+        assert_eq!(minimise(&input, &bumpy), brute_force(&input, &bumpy));
+
+        for input in random_inputs() {
+            assert_eq!(minimise(&input, &Linear), brute_force(&input, &Linear));
+            assert_eq!(
+                minimise(&input, &Triangular),
+                brute_force(&input, &Triangular)
+            );
+            assert_eq!(
+                minimise(&input, &Quadratic),
+                brute_force(&input, &Quadratic)
+            );
+            assert_eq!(
+                minimise(&input, &Convex(|d: i64| d.pow(4) + 5 * d)),
+                brute_force(&input, &|d: i64| d.pow(4) + 5 * d)
+            );
+        }
+
+        assert_eq!(minimise(&[], &Quadratic), None);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::Fallible;
+
 pub type InputElem = ([u8; 10], [u8; 4]);
 
 pub fn parse_input(raw: &str) -> Vec<InputElem> {
@@ -29,76 +31,361 @@ pub fn parse_input(raw: &str) -> Vec<InputElem> {
     raw.lines().map(parse_line).collect()
 }
 
-#[derive(Debug)]
-pub struct SolvedMap {
-    digits: [u8; 10],
+/// The segments lit for each digit on a standard display, bit 0 being segment 'a'
+pub const STANDARD_DIGITS: [u8; 10] = [
+    0b1110111, // 0: abcefg
+    0b0100100, // 1: cf
+    0b1011101, // 2: acdeg
+    0b1101101, // 3: acdfg
+    0b0101110, // 4: bcdf
+    0b1101011, // 5: abdfg
+    0b1111011, // 6: abdefg
+    0b0100101, // 7: acf
+    0b1111111, // 8: abcdefg
+    0b1101111, // 9: abcdfg
+];
+
+/// Which segment each of the scrambled wires is connected to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WireMapping {
+    /// The segment index for each wire index
+    pub segments: Vec<u8>,
 }
 
-impl SolvedMap {
-    /// Returns the map values, but sorted such that the first element is the value for the digit zero
-    pub fn from_input(map: [u8; 10]) -> Self {
-        let mut out = Self { digits: [0; 10] };
-
-        // Fill in the easy codes, (1, 4, 7, and 8)
-        for val in &map {
-            match val.count_ones() {
-                2 => out.digits[1] = *val,
-                3 => out.digits[7] = *val,
-                4 => out.digits[4] = *val,
-                7 => out.digits[8] = *val,
-                _ => (),
-            }
+impl WireMapping {
+    /// The segments lit by a pattern of scrambled wires
+    pub fn translate(&self, wires: u8) -> u8 {
+        self.segments
+            .iter()
+            .take(MAX_WIRES)
+            .enumerate()
+            .fold(0, |segments, (wire, segment)| {
+                segments | ((wires >> wire) & 1) << segment
+            })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WiringError {
+    /// No wiring turns every pattern into a digit from the table
+    Inconsistent,
+
+    /// More than one wiring fits the patterns, two of which are given
+    Ambiguous(WireMapping, WireMapping),
+
+    /// A pattern that isn't any of the digits under the solved wiring
+    UnknownPattern(u8),
+}
+
+impl std::fmt::Display for WiringError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WiringError::Inconsistent => write!(f, "no wiring fits the patterns"),
+            WiringError::Ambiguous(a, b) => write!(
+                f,
+                "multiple wirings fit the patterns, including {:?} and {:?}",
+                a.segments, b.segments
+            ),
+            WiringError::UnknownPattern(p) => write!(f, "pattern {:#09b} is not a digit", p),
         }
+    }
+}
 
-        // Of the 5 segment numbers (2, 3, 5), only 3 has (digit1 & digit3) == digit1
-        out.digits[3] = *map
-            .iter()
-            .filter(|v| v.count_ones() == 5)
-            .filter(|v| *v & out.digits[1] == out.digits[1])
-            .next()
-            .unwrap();
+impl std::error::Error for WiringError {}
 
-        // Of the 6 segment numbers (0, 6, 9), only 6 has (digit1 & digit6) != digit1
-        out.digits[6] = *map
-            .iter()
-            .filter(|v| v.count_ones() == 6)
-            .filter(|v| *v & out.digits[1] != out.digits[1])
-            .next()
-            .unwrap();
-
-        // Of the remaining 6 segment numbers (0, 9), only 9 has (digit9 & digit4) == digit4
-        let six = out.digits[6];
-        for val in map
-            .iter()
-            .filter(|v| v.count_ones() == 6)
-            .filter(|v| **v != six)
-        {
-            if *val & out.digits[4] == out.digits[4] {
-                out.digits[9] = *val;
-            } else {
-                out.digits[0] = *val;
+/// The most wires a display can have, one per bit of a pattern
+const MAX_WIRES: usize = 8;
+
+/// The most digits a table can have, one per bit of a candidate mask
+const MAX_DIGITS: usize = 32;
+
+/// The remaining possibilities during a solve, in fixed size arrays so that branching in the search
+/// is a plain copy
+#[derive(Clone, Copy, Debug)]
+struct Candidates {
+    /// A bitmask of possible segments for each wire, of which only the first `size` are used
+    wires: [u8; MAX_WIRES],
+
+    /// A bitmask of possible table indices for each pattern
+    digits: [u32; MAX_DIGITS],
+}
+
+struct WiringSolver<'a> {
+    table: &'a [u8],
+    patterns: &'a [u8],
+
+    /// The number of wires, which is also the number of segments
+    size: usize,
+    all_segments: u8,
+}
+
+/// For each wire or segment, how many of the patterns of each size include it, packed into 7 bits
+/// per size
+fn signatures(patterns: &[u8]) -> [u64; MAX_WIRES] {
+    let mut signatures = [0; MAX_WIRES];
+    for p in patterns {
+        let weight = 1 << (7 * p.count_ones());
+        for (bit, signature) in signatures.iter_mut().enumerate() {
+            *signature += weight * ((p >> bit) & 1) as u64;
+        }
+    }
+    signatures
+}
+
+/// The indices of the set bits in a mask, lowest first
+fn bits(mut mask: u32) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        let bit = mask.trailing_zeros();
+        mask &= mask.wrapping_sub(1);
+        (bit < 32).then_some(bit as usize)
+    })
+}
+
+impl<'a> WiringSolver<'a> {
+    /// Narrows down the candidates until nothing more can be deduced without guessing. Returns
+    /// false if the candidates turn out to be contradictory.
+    fn propagate(&self, c: &mut Candidates) -> bool {
+        loop {
+            let mut changed = false;
+
+            for (i, pattern) in self.patterns.iter().enumerate() {
+                // Drop digits that another pattern has taken, whose segments can't all be driven
+                // by the pattern's wires, or that some wire can't agree with
+                let reach = (0..self.size)
+                    .filter(|w| pattern & (1 << w) != 0)
+                    .fold(0, |acc, w| acc | c.wires[w]);
+                let taken = c.digits[..self.patterns.len()]
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, other)| *j != i && other.count_ones() == 1)
+                    .fold(0, |acc, (_, other)| acc | other);
+                let mut digits = c.digits[i] & !taken;
+                for d in bits(digits) {
+                    let lit = self.table[d];
+                    let fits = reach & lit == lit
+                        && (0..self.size).all(|w| {
+                            let needed = if pattern & (1 << w) != 0 {
+                                lit
+                            } else {
+                                !lit & self.all_segments
+                            };
+                            c.wires[w] & needed != 0
+                        });
+                    if !fits {
+                        digits &= !(1 << d);
+                    }
+                }
+
+                if digits == 0 {
+                    return false;
+                }
+                changed |= digits != c.digits[i];
+                c.digits[i] = digits;
+
+                // The wires in the pattern must drive one of the segments lit by its candidate
+                // digits, and those outside it one of the unlit segments
+                let lit = bits(digits).fold(0, |acc, d| acc | self.table[d]);
+                let unlit = bits(digits).fold(0, |acc, d| acc | (!self.table[d]));
+                for w in 0..self.size {
+                    let allowed = if pattern & (1 << w) != 0 { lit } else { unlit };
+                    let narrowed = c.wires[w] & allowed;
+                    if narrowed == 0 {
+                        return false;
+                    }
+                    changed |= narrowed != c.wires[w];
+                    c.wires[w] = narrowed;
+                }
+            }
+
+            // Each segment is driven by exactly one wire
+            for w in 0..self.size {
+                if c.wires[w].count_ones() == 1 {
+                    for v in (0..self.size).filter(|v| *v != w) {
+                        if c.wires[v] & c.wires[w] != 0 {
+                            c.wires[v] &= !c.wires[w];
+                            if c.wires[v] == 0 {
+                                return false;
+                            }
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            for s in 0..self.size {
+                let mut able = (0..self.size).filter(|w| c.wires[*w] & (1 << s) != 0);
+                match (able.next(), able.next()) {
+                    (None, _) => return false,
+                    (Some(w), None) if c.wires[w] != 1 << s => {
+                        c.wires[w] = 1 << s;
+                        changed = true;
+                    }
+                    _ => (),
+                }
+            }
+
+            if !changed {
+                return true;
             }
         }
+    }
 
-        // Of the remaning 5 segment numbers (2, 5), only 5 has (digit5 & digit6) == digit5
-        let three = out.digits[3];
-        for val in map
-            .iter()
-            .filter(|v| v.count_ones() == 5)
-            .filter(|v| **v != three)
-        {
-            if *val & six == *val {
-                out.digits[5] = *val;
-            } else {
-                out.digits[2] = *val;
+    /// With every digit seen, each wire has to drive the segment that is part of as many digits of
+    /// each size as the wire is of patterns. If that tells the segments apart, it gives the only
+    /// possible wiring without any search.
+    fn by_signature(&self) -> Option<WireMapping> {
+        if self.patterns.len() != self.table.len() {
+            return None;
+        }
+
+        let segments = signatures(self.table);
+        let segments = &segments[..self.size];
+        if (1..self.size).any(|s| segments[..s].contains(&segments[s])) {
+            return None;
+        }
+
+        let mapping = WireMapping {
+            segments: signatures(self.patterns)[..self.size]
+                .iter()
+                .map(|sig| segments.iter().position(|s| s == sig).map(|s| s as u8))
+                .collect::<Option<_>>()?,
+        };
+        self.verify(&mapping).then_some(mapping)
+    }
+
+    /// Checks a complete assignment against the patterns, rather than relying on the propagation
+    fn verify(&self, mapping: &WireMapping) -> bool {
+        // Sets of 8 bit patterns, as a bit per pattern
+        let mut digits = [0u128; 2];
+        for d in self.table {
+            digits[*d as usize / 128] |= 1 << (d % 128);
+        }
+
+        let mut used = [0u128; 2];
+        let mut fits = true;
+        for pattern in self.patterns {
+            let lit = mapping.translate(*pattern);
+            let (half, bit) = (lit as usize / 128, 1 << (lit % 128));
+            fits &= digits[half] & bit != 0 && used[half] & bit == 0;
+            used[half] |= bit;
+        }
+        fits
+    }
+
+    /// Collects solutions into `found`, stopping once there are two
+    fn search(&self, mut c: Candidates, found: &mut Vec<WireMapping>) {
+        if !self.propagate(&mut c) {
+            return;
+        }
+
+        let branch = (0..self.size)
+            .filter(|w| c.wires[*w].count_ones() > 1)
+            .min_by_key(|w| c.wires[*w].count_ones());
+
+        match branch {
+            None => {
+                let mapping = WireMapping {
+                    segments: c.wires[..self.size]
+                        .iter()
+                        .map(|s| s.trailing_zeros() as u8)
+                        .collect(),
+                };
+                if self.verify(&mapping) {
+                    found.push(mapping);
+                }
+            }
+            Some(w) => {
+                for s in bits(c.wires[w] as u32) {
+                    let mut guess = c;
+                    guess.wires[w] = 1 << s;
+                    self.search(guess, found);
+                    if found.len() >= 2 {
+                        return;
+                    }
+                }
             }
         }
+    }
+}
 
-        out
+/// Finds how the wires are connected to the segments, given the scrambled patterns seen on a
+/// display and the segments lit for each digit.
+///
+/// Every distinct pattern must be a different digit from the table. The number of wires is taken
+/// to be the highest segment used by the table, up to 8.
+pub fn solve_wiring(table: &[u8], patterns: &[u8]) -> Result<WireMapping, WiringError> {
+    assert!(table.len() <= MAX_DIGITS, "Expected at most 32 digits");
+
+    let all_used = table.iter().fold(0, |acc, d| acc | d);
+    let size = 8 - all_used.leading_zeros() as usize;
+    let all_segments = (0xffu16 >> (8 - size)) as u8;
+
+    // Each distinct pattern needs a digit of its own, so there can't be more of them than digits
+    let mut distinct = [0; MAX_DIGITS];
+    let mut count = 0;
+    for p in patterns {
+        if distinct[..count].contains(p) {
+            continue;
+        }
+        if p & !all_segments != 0 || count == table.len() {
+            return Err(WiringError::Inconsistent);
+        }
+        distinct[count] = *p;
+        count += 1;
+    }
+
+    let solver = WiringSolver {
+        table,
+        patterns: &distinct[..count],
+        size,
+        all_segments,
+    };
+    if let Some(mapping) = solver.by_signature() {
+        return Ok(mapping);
+    }
+
+    let mut candidates = Candidates {
+        wires: [all_segments; MAX_WIRES],
+        digits: [0; MAX_DIGITS],
+    };
+    for (digits, p) in candidates.digits.iter_mut().zip(solver.patterns) {
+        *digits = (0..table.len())
+            .filter(|d| table[*d].count_ones() == p.count_ones())
+            .fold(0, |acc, d| acc | (1 << d));
+    }
+
+    let mut found = Vec::new();
+    solver.search(candidates, &mut found);
+
+    let mut found = found.into_iter();
+    match (found.next(), found.next()) {
+        (None, _) => Err(WiringError::Inconsistent),
+        (Some(mapping), None) => Ok(mapping),
+        (Some(a), Some(b)) => Err(WiringError::Ambiguous(a, b)),
+    }
+}
+
+#[derive(Debug)]
+pub struct SolvedMap {
+    wiring: WireMapping,
+}
+
+impl SolvedMap {
+    pub fn from_input(map: [u8; 10]) -> Result<Self, WiringError> {
+        let wiring = solve_wiring(&STANDARD_DIGITS, &map)?;
+        Ok(Self { wiring })
+    }
+
+    pub fn wiring(&self) -> &WireMapping {
+        &self.wiring
     }
 
-    pub fn value(&self, val: u8) -> u8 {
-        self.digits.iter().position(|d| *d == val).unwrap() as u8
+    pub fn value(&self, val: u8) -> Result<u8, WiringError> {
+        let segments = self.wiring.translate(val);
+        STANDARD_DIGITS
+            .iter()
+            .position(|d| *d == segments)
+            .map(|d| d as u8)
+            .ok_or(WiringError::UnknownPattern(val))
     }
 }
 
@@ -116,19 +403,23 @@ pub fn part_1(input: &[([u8; 10], [u8; 4])]) -> i32 {
         .sum::<usize>() as i32
 }
 
-pub fn part_2(input: &[([u8; 10], [u8; 4])]) -> i32 {
+pub fn try_part_2(input: &[([u8; 10], [u8; 4])]) -> Result<i32, WiringError> {
     let mut ans = 0;
     for (map, code) in input {
-        let map = SolvedMap::from_input(*map);
+        let map = SolvedMap::from_input(*map)?;
 
-        let code = map.value(code[0]) as i32 * 1000
-            + map.value(code[1]) as i32 * 100
-            + map.value(code[2]) as i32 * 10
-            + map.value(code[3]) as i32 * 1;
+        let code = map.value(code[0])? as i32 * 1000
+            + map.value(code[1])? as i32 * 100
+            + map.value(code[2])? as i32 * 10
+            + map.value(code[3])? as i32;
         ans += code;
     }
 
-    ans
+    Ok(ans)
+}
+
+pub fn part_2(input: &[([u8; 10], [u8; 4])]) -> Fallible<i32, WiringError> {
+    Fallible(try_part_2(input))
 }
 
 impl_day!(
@@ -137,5 +428,135 @@ impl_day!(
     "Seven Segment Search",
     Vec<InputElem>,
     i32,
-    i32
+    Fallible<i32, WiringError>
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_input() -> Vec<InputElem> {
+        parse_input(
+            "be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
+edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc
+fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg
+fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb
+aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea
+fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb
+dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe
+bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef
+egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
+gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce",
+        )
+    }
+
+    fn pattern(s: &str) -> u8 {
+        s.bytes().fold(0, |acc, c| acc | (1 << (c - b'a')))
+    }
+
+    #[test]
+    fn test_example() {
+        let input = example_input();
+        assert_eq!(part_1(&input), 26);
+        assert_eq!(part_2(&input), Fallible(Ok(61229)));
+    }
+
+    #[test]
+    fn test_wire_mapping() {
+        let input = parse_input(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+        );
+        let (map, code) = input[0];
+        let solved = SolvedMap::from_input(map).unwrap();
+
+        // Wires a to g drive segments c, f, g, a, b, d, e
+        assert_eq!(solved.wiring().segments, vec![2, 5, 6, 0, 1, 3, 4]);
+        let digits = code.map(|c| solved.value(c).unwrap());
+        assert_eq!(digits, [5, 3, 5, 3]);
+
+        // Without 8, which lights every segment, the search has to find the same wiring
+        let without_eight = map.map(|p| if p.count_ones() == 7 { map[0] } else { p });
+        assert_eq!(
+            solve_wiring(&STANDARD_DIGITS, &without_eight).as_ref(),
+            Ok(solved.wiring())
+        );
+    }
+
+    #[test]
+    fn test_recovers_permutations() {
+        // Scramble the standard digits with a few different wirings, and solve them back
+        let wirings: [[u8; 7]; 4] = [
+            [0, 1, 2, 3, 4, 5, 6],
+            [6, 5, 4, 3, 2, 1, 0],
+            [2, 5, 6, 0, 1, 3, 4],
+            [1, 0, 3, 2, 5, 4, 6],
+        ];
+        for segments in wirings {
+            let wiring = WireMapping {
+                segments: segments.to_vec(),
+            };
+            let scrambled = STANDARD_DIGITS.map(|digit| {
+                (0..7)
+                    .filter(|w| digit & (1 << segments[*w]) != 0)
+                    .fold(0, |acc, w| acc | (1 << w))
+            });
+            for d in 0..10 {
+                assert_eq!(wiring.translate(scrambled[d]), STANDARD_DIGITS[d]);
+            }
+            assert_eq!(solve_wiring(&STANDARD_DIGITS, &scrambled), Ok(wiring));
+        }
+    }
+
+    #[test]
+    fn test_custom_table() {
+        // A four segment display, where each digit adds one more segment
+        let table = [0b0001, 0b0011, 0b0111, 0b1111, 0b1010];
+        let patterns = [
+            pattern("c"),
+            pattern("cd"),
+            pattern("cda"),
+            pattern("abcd"),
+            pattern("db"),
+        ];
+        assert_eq!(
+            solve_wiring(&table, &patterns),
+            Ok(WireMapping {
+                segments: vec![2, 3, 0, 1]
+            })
+        );
+    }
+
+    #[test]
+    fn test_wiring_errors() {
+        // Only 1 and 8 say nothing about which of c and f is which, among others
+        let err = solve_wiring(&STANDARD_DIGITS, &[pattern("ab"), pattern("abcdefg")]);
+        assert!(matches!(err, Err(WiringError::Ambiguous(_, _))));
+
+        // Two different patterns can't both be the only two segment digit
+        assert_eq!(
+            solve_wiring(&STANDARD_DIGITS, &[pattern("ab"), pattern("cd")]),
+            Err(WiringError::Inconsistent)
+        );
+
+        // Repeats of the same pattern are fine
+        let mut map = example_input()[0].0;
+        map[1] = map[0];
+        assert!(solve_wiring(&STANDARD_DIGITS, &map).is_ok());
+
+        // A five segment pattern that doesn't fit with the rest
+        let mut map = example_input()[0].0;
+        let five = map.iter().position(|p| p.count_ones() == 5).unwrap();
+        map[five] = pattern("abcde");
+        assert_eq!(
+            solve_wiring(&STANDARD_DIGITS, &map),
+            Err(WiringError::Inconsistent)
+        );
+
+        assert_eq!(
+            SolvedMap::from_input(example_input()[0].0)
+                .unwrap()
+                .value(pattern("ab")),
+            Err(WiringError::UnknownPattern(pattern("ab")))
+        );
+    }
+}
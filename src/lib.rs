@@ -72,6 +72,37 @@ pub trait Day {
     }
 }
 
+/// An answer that may have failed, displayed as either the answer or the error that prevented it.
+///
+/// Lets a day report a failure in the results table rather than panicking.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fallible<T, E>(pub Result<T, E>);
+
+impl<T: std::fmt::Display, E: std::fmt::Display> std::fmt::Display for Fallible<T, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Ok(answer) => write!(f, "{}", answer),
+            Err(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+/// A small linear congruential generator, for reproducible synthetic inputs in tests and benches
+#[derive(Clone, Debug)]
+pub struct Lcg(u32);
+
+impl Lcg {
+    pub fn new(seed: u32) -> Self {
+        Self(seed)
+    }
+
+    /// The next value, taken from the high bits as the low bits of an LCG are poorly distributed
+    pub fn next_u16(&mut self) -> u16 {
+        self.0 = self.0.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (self.0 >> 16) as u16
+    }
+}
+
 pub struct ErasedDay {
     pub name: DayName,
     pub run: Box<dyn Fn() -> RunResult>,
@@ -122,6 +153,11 @@ pub mod day_16;
 pub mod day_17;
 pub mod day_18;
 pub mod day_2;
+pub mod day_21;
+pub mod day_22;
+pub mod day_23;
+pub mod day_24;
+pub mod day_25;
 pub mod day_3;
 pub mod day_4;
 pub mod day_5;
@@ -150,5 +186,10 @@ pub fn all_days() -> Vec<ErasedDay> {
         day_16::Day::erased(),
         day_17::Day::erased(),
         day_18::Day::erased(),
+        day_21::Day::erased(),
+        day_22::Day::erased(),
+        day_23::Day::erased(),
+        day_24::Day::erased(),
+        day_25::Day::erased(),
     ]
 }
@@ -1,6 +1,10 @@
-use std::time::Duration;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use aoc_2021::{all_days, RunResult};
+use aoc_2021::{all_days, Day, RunResult};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -13,6 +17,21 @@ struct Opt {
     /// Filter for answers on the given day
     #[structopt(name = "DAY", long = "day")]
     day: Option<String>,
+
+    /// Instead of running the solutions, write a picture of the given day's puzzle input to this
+    /// file. Only supported for day 5.
+    #[structopt(name = "RENDER", long = "render", parse(from_os_str))]
+    render: Option<PathBuf>,
+
+    /// Instead of running the solutions, print how many lanternfish (day 6) there are after this
+    /// many days
+    #[structopt(name = "FISH_DAYS", long = "fish-days")]
+    fish_days: Option<u64>,
+
+    /// Along with --fish-days, also write the population on every day up to then to this file as
+    /// CSV
+    #[structopt(name = "FISH_CSV", long = "fish-csv", parse(from_os_str))]
+    fish_csv: Option<PathBuf>,
 }
 
 fn print_results(results: &[RunResult]) {
@@ -102,8 +121,71 @@ fn print_results(results: &[RunResult]) {
     );
 }
 
+fn render(opt: &Opt, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use aoc_2021::day_5;
+
+    match opt.day.as_deref() {
+        Some("5") => {
+            let lines = day_5::parse_input(day_5::Day::REAL_INPUT);
+            let map = day_5::DensityMap::build(&lines, day_5::Raster::Exact)?;
+            let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+            map.render(&mut out)?;
+            out.flush()?;
+            println!(
+                "Wrote {}x{} density map to {}",
+                map.width(),
+                map.height(),
+                path.display()
+            );
+        }
+        _ => return Err("rendering is only supported for --day 5".into()),
+    }
+
+    Ok(())
+}
+
+fn count_fish(days: u64, csv_path: Option<&Path>) -> std::io::Result<()> {
+    use aoc_2021::day_6;
+
+    let state = day_6::parse_input(day_6::Day::REAL_INPUT);
+    let count = state
+        .count_after::<num_bigint::BigUint>(days)
+        .expect("Arbitrary precision counts can't overflow");
+    println!("After {} days there are {} lanternfish", days, count);
+
+    if let Some(path) = csv_path {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let series = state.time_series::<num_bigint::BigUint>(days);
+        day_6::write_time_series_csv(series, &mut out)?;
+        out.flush()?;
+        println!("Wrote population time series to {}", path.display());
+    }
+
+    Ok(())
+}
+
 fn main() {
     let opt = Opt::from_args();
+
+    if let Some(days) = opt.fish_days {
+        if let Err(e) = count_fish(days, opt.fish_csv.as_deref()) {
+            eprintln!("Failed to write fish CSV: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    } else if opt.fish_csv.is_some() {
+        println!("--fish-csv requires --fish-days");
+        return;
+    }
+
+    if let Some(path) = &opt.render {
+        if let Err(e) = render(&opt, path) {
+            eprintln!("Failed to render to {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let mut solutions = all_days();
 
     solutions = solutions